@@ -1,15 +1,20 @@
 use crate::utils;
+use image::{DynamicImage, Rgb};
+use std::sync::Arc;
 
 /// Configuration struct to customize printing behaviour.
 #[derive(Debug, Clone)]
 pub struct Config {
-    /// Enable true transparency instead of checkerboard background.
+    /// Enable true transparency instead of the [backdrop](Config::backdrop).
     /// Available only for the block printer. Defaults to false.
     pub transparent: bool,
     /// If we assume the alpha channel is premultiplied for blending with the
-    /// checkerboard background.
+    /// backdrop.
     /// Defaults to false.
     pub premultiplied_alpha: bool,
+    /// Background that (semi-)transparent pixels are blended with. Used by the block
+    /// and sixel printers. Defaults to a gray checkerboard.
+    pub backdrop: Backdrop,
     /// Make the x and y offset be relative to the top left terminal corner.
     /// If false, the y offset is relative to the cursor's position.
     /// Defaults to true.
@@ -19,17 +24,71 @@ pub struct Config {
     /// Y offset. Can be negative only when `absolute_offset` is `false`.
     /// Defaults to 0.
     pub y: i16,
+    /// Position of the image within the terminal, or within `align_area` if set. The `x`
+    /// and `y` offsets are added on top. Defaults to None, placing the image at the offsets.
+    pub align: Option<Align>,
+    /// Area the image is aligned in when `align` is set, in terminal cells. Its position is
+    /// interpreted like the `x` and `y` offsets. Defaults to None, which is the whole
+    /// terminal, except for its last line.
+    pub align_area: Option<Rect>,
+    /// What happens to images that do not fit in the terminal at their position.
+    /// Defaults to [Overflow::Scroll].
+    pub overflow: Overflow,
+    /// Border drawn around the image with box-drawing characters. Defaults to None.
+    pub border: Option<Border>,
+    /// Empty cells between the image and its border on each side. Defaults to 0.
+    pub padding: u32,
+    /// Line of text printed above or below the image. Defaults to None.
+    pub caption: Option<Caption>,
     /// Take a note of cursor position before printing and restore it when finished.
     /// Defaults to false.
     pub restore_cursor: bool,
+    /// Rotate and flip images decoded from files according to their EXIF orientation, like
+    /// photos taken with a phone. Only used when printing files. Defaults to true.
+    pub exif_orientation: bool,
+    /// Clockwise rotation applied to the image before it is sized. Defaults to [Rotation::None].
+    pub rotate: Rotation,
+    /// Mirror the image left to right, after rotating it. Defaults to false.
+    pub flip_horizontal: bool,
+    /// Mirror the image top to bottom, after rotating it. Defaults to false.
+    pub flip_vertical: bool,
+    /// Region of the (rotated) image to print, in pixels. Everything else is treated as if it were not
+    /// part of the image. Defaults to None, printing the whole image.
+    pub source_rect: Option<Rect>,
     /// Optional image width. Defaults to None.
-    pub width: Option<u32>,
+    pub width: Option<Size>,
     /// Optional image height. Defaults to None.
-    pub height: Option<u32>,
+    pub height: Option<Size>,
+    /// How the image is sized to fit in the box given by `width` and `height`. Missing
+    /// dimensions are taken from the terminal size. Defaults to [Fit::Auto].
+    pub fit: Fit,
+    /// Position of the image within the box, when their aspect ratios differ.
+    /// Only used when `fit` is not [Fit::Auto]. Defaults to centered.
+    pub fit_align: Align,
+    /// Whether images smaller than the available space are scaled up. Only used when `fit`
+    /// is [Fit::Auto]. Defaults to [Upscale::Never].
+    pub upscale: Upscale,
+    /// Resampling used when an image is enlarged. Only the block and sixel printers resize
+    /// images themselves, graphics protocols leave it to the terminal.
+    /// Defaults to [UpscaleFilter::Smooth].
+    pub upscale_filter: UpscaleFilter,
+    /// Resampling filter used when resizing images. Like `upscale_filter`, it only applies
+    /// to the block and sixel printers. Defaults to [Filter::Auto].
+    pub filter: Filter,
+    /// Resize in linear light instead of sRGB space. This is gamma-correct and keeps
+    /// downscaled high-contrast images from getting darker, but is slower.
+    /// Defaults to false.
+    pub linear_light: bool,
+    /// Size of the terminal in cells, which sizes and positions are derived from, instead of
+    /// querying the terminal. Defaults to None.
+    pub terminal_size: Option<(u16, u16)>,
     /// Use truecolor if the terminal supports it. Defaults to true.
     pub truecolor: bool,
     /// Use Kitty protocol if the terminal supports it. Defaults to true.
     pub use_kitty: bool,
+    /// Image id for the Kitty protocol. Printing an image with the id of a previous one
+    /// replaces it, instead of adding another image. Defaults to None.
+    pub kitty_image_id: Option<u32>,
     /// Use iTerm protocol if the terminal supports it. Defaults to true.
     pub use_iterm: bool,
     /// Use Sixel protocol if the terminal supports it. Defaults to true.
@@ -42,17 +101,328 @@ impl std::default::Default for Config {
         Self {
             transparent: false,
             premultiplied_alpha: false,
+            backdrop: Backdrop::default(),
             absolute_offset: true,
             x: 0,
             y: 0,
+            align: None,
+            align_area: None,
+            overflow: Overflow::Scroll,
+            border: None,
+            padding: 0,
+            caption: None,
             restore_cursor: false,
+            exif_orientation: true,
+            rotate: Rotation::None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            source_rect: None,
             width: None,
             height: None,
+            fit: Fit::Auto,
+            fit_align: Align::default(),
+            upscale: Upscale::Never,
+            upscale_filter: UpscaleFilter::Smooth,
+            filter: Filter::Auto,
+            linear_light: false,
+            terminal_size: None,
             truecolor: utils::truecolor_available(),
             use_kitty: true,
+            kitty_image_id: None,
             use_iterm: true,
             #[cfg(any(feature = "sixel", feature = "icy_sixel"))]
             use_sixel: true,
         }
     }
 }
+
+impl Config {
+    /// The configured terminal size, or the actual one.
+    pub(crate) fn terminal_size(&self) -> (u16, u16) {
+        self.terminal_size.unwrap_or_else(utils::terminal_size)
+    }
+}
+
+/// A length along one axis of the terminal, see [Config::width] and [Config::height].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    /// A number of terminal cells.
+    Cells(u32),
+    /// A number of pixels on the screen, converted to whole cells using the size of a cell
+    /// reported by the terminal.
+    Pixels(u32),
+    /// A percentage of the terminal, or of the `align_area` if one is set.
+    Percent(u32),
+}
+
+impl Size {
+    /// Number of cells, given the `available` cells along this axis and the size of a
+    /// cell in pixels. Never less than a single cell.
+    pub(crate) fn cells(self, available: u32, cell_pixels: u32) -> u32 {
+        let cells = match self {
+            Size::Cells(cells) => cells,
+            Size::Pixels(pixels) => pixels / std::cmp::max(1, cell_pixels),
+            Size::Percent(percent) => (available as u64 * percent as u64 / 100) as u32,
+        };
+        std::cmp::max(1, cells)
+    }
+}
+
+impl From<u32> for Size {
+    fn from(cells: u32) -> Self {
+        Size::Cells(cells)
+    }
+}
+
+/// Style of the border around an image, see [Config::border].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Border {
+    /// Thin lines with square corners: ┌─┐
+    Single,
+    /// Thin lines with rounded corners: ╭─╮
+    Rounded,
+    /// Thick lines: ┏━┓
+    Heavy,
+    /// Double lines: ╔═╗
+    Double,
+}
+
+/// A line of text printed together with an image, like its file name and dimensions.
+/// Text that does not fit in the width of the image is cut off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caption {
+    /// The text, which should not contain line breaks.
+    pub text: String,
+    /// Whether the caption is printed above or below the image.
+    pub position: CaptionPosition,
+    /// Alignment of the text in the line.
+    pub align: HorizontalAlign,
+}
+
+impl Caption {
+    /// A centered caption below the image.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            position: CaptionPosition::Below,
+            align: HorizontalAlign::Center,
+        }
+    }
+}
+
+/// Where a [Caption] is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptionPosition {
+    /// On the line above the image and its border.
+    Above,
+    /// On the line below the image and its border.
+    #[default]
+    Below,
+}
+
+/// Handling of images that extend beyond the right or bottom edge of the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Print the whole image, letting the terminal wrap lines and scroll as it sees fit.
+    #[default]
+    Scroll,
+    /// Print only the part of the image that is visible. The bottom edge is only known with
+    /// an absolute offset, where the last line is kept free so that the terminal does not
+    /// scroll. Images that are not visible at all cause an error.
+    Clip,
+}
+
+/// Clockwise rotation of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    /// Keep the image as it is.
+    #[default]
+    None,
+    /// Rotate by 90 degrees.
+    Deg90,
+    /// Rotate by 180 degrees.
+    Deg180,
+    /// Rotate by 270 degrees, or 90 degrees counter-clockwise.
+    Deg270,
+}
+
+/// An axis-aligned rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    /// Horizontal position of the left edge.
+    pub x: u32,
+    /// Vertical position of the top edge.
+    pub y: u32,
+    /// Width of the rectangle.
+    pub width: u32,
+    /// Height of the rectangle.
+    pub height: u32,
+}
+
+impl Rect {
+    /// Create a rectangle from its position and size.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// Background drawn behind the transparent parts of an image.
+///
+/// Sizes are measured in half cells, the resolution of the block printer. One terminal cell
+/// is therefore 1x2 units, regardless of the printer that ends up being used.
+#[derive(Debug, Clone)]
+pub enum Backdrop {
+    /// A single solid color.
+    Solid(Rgb<u8>),
+    /// A checkerboard alternating between two colors, starting with `dark` in the top
+    /// left corner.
+    Checkerboard {
+        /// Color of the lighter tiles.
+        light: Rgb<u8>,
+        /// Color of the darker tiles.
+        dark: Rgb<u8>,
+        /// Edge length of a single tile. A value of 0 is treated as 1.
+        size: u32,
+    },
+    /// An image that is stretched to the size of the printed image.
+    Image(Arc<DynamicImage>),
+}
+
+impl std::default::Default for Backdrop {
+    fn default() -> Self {
+        Backdrop::Checkerboard {
+            light: Rgb([153, 153, 153]),
+            dark: Rgb([102, 102, 102]),
+            size: 1,
+        }
+    }
+}
+
+/// Strategy for fitting an image in a box of terminal cells, modelled after the CSS
+/// `object-fit` property.
+///
+/// Except for [Fit::Auto], the dimensions returned when printing are those of the whole box,
+/// so that images can be laid out in tiles of a fixed size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// The behaviour of [crate::resize]: stretch when both dimensions are given, otherwise
+    /// scale down while preserving the aspect ratio. The box is as big as the image.
+    #[default]
+    Auto,
+    /// Scale up or down to the largest size that fits in the box, preserving the aspect ratio.
+    /// The image is letterboxed.
+    Contain,
+    /// Scale up or down to the smallest size that covers the whole box, preserving the aspect
+    /// ratio. Whatever does not fit is cropped.
+    Cover,
+    /// Stretch the image to the size of the box.
+    Fill,
+    /// Keep the original size, one pixel per half cell. Whatever does not fit is cropped.
+    None,
+    /// The smaller of [Fit::None] and [Fit::Contain].
+    ScaleDown,
+}
+
+/// Policy for images which are smaller than the space available to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Upscale {
+    /// Print small images in their original size.
+    #[default]
+    Never,
+    /// Scale small images up to the available space, preserving the aspect ratio.
+    Always,
+    /// Scale small images up by the largest whole number that fits, so that every pixel
+    /// is repeated the same number of times. Best suited for pixel art.
+    Integer,
+}
+
+/// Resampling filter used when enlarging an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpscaleFilter {
+    /// Repeat pixels, keeping edges sharp. Best suited for pixel art.
+    Nearest,
+    /// Interpolate between pixels, with the filter from [Config::filter].
+    #[default]
+    Smooth,
+}
+
+/// Resampling filter used when resizing an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// Catmull-Rom for the block printer and a linear filter for sixel.
+    #[default]
+    Auto,
+    /// Nearest neighbour.
+    Nearest,
+    /// Average all source pixels covered by a target pixel. Best suited for downscaling
+    /// large images.
+    Box,
+    /// Linear filter.
+    Triangle,
+    /// Cubic filter.
+    CatmullRom,
+    /// Gaussian filter.
+    Gaussian,
+    /// Lanczos filter with a window of 3.
+    Lanczos3,
+}
+
+/// Alignment of an image within a larger area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Align {
+    /// Horizontal alignment. Defaults to [HorizontalAlign::Center].
+    pub horizontal: HorizontalAlign,
+    /// Vertical alignment. Defaults to [VerticalAlign::Middle].
+    pub vertical: VerticalAlign,
+}
+
+/// Horizontal alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HorizontalAlign {
+    /// Align to the left edge.
+    Left,
+    /// Center horizontally.
+    #[default]
+    Center,
+    /// Align to the right edge.
+    Right,
+}
+
+/// Vertical alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    /// Align to the top edge.
+    Top,
+    /// Center vertically.
+    #[default]
+    Middle,
+    /// Align to the bottom edge.
+    Bottom,
+}
+
+impl HorizontalAlign {
+    /// Offset from the left edge, given the free horizontal space.
+    pub(crate) fn offset(self, free: u32) -> u32 {
+        match self {
+            HorizontalAlign::Left => 0,
+            HorizontalAlign::Center => free / 2,
+            HorizontalAlign::Right => free,
+        }
+    }
+}
+
+impl VerticalAlign {
+    /// Offset from the top edge, given the free vertical space.
+    pub(crate) fn offset(self, free: u32) -> u32 {
+        match self {
+            VerticalAlign::Top => 0,
+            VerticalAlign::Middle => free / 2,
+            VerticalAlign::Bottom => free,
+        }
+    }
+}
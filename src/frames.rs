@@ -0,0 +1,273 @@
+use crate::config::Config;
+use crate::error::ViuResult;
+use crate::printer::layout::Layout;
+use crate::printer::orientation::orient;
+use crate::printer::{BlockRenderer, Printer, PrinterType, ReadKey, DEFAULT_KITTY_IMAGE_ID};
+use console::Term;
+use crossterm::{cursor::MoveToPreviousLine, execute};
+use image::DynamicImage;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use termcolor::Ansi;
+
+/// Prints a stream of frames in place, like a video, at no more than a target frame rate.
+///
+/// Frames that arrive early are held back until it is their turn. If printing takes longer
+/// than a frame, as many of the following frames as it took too long are dropped, so that the
+/// output does not fall behind. Each frame replaces the previous one in the cheapest way the
+/// printer allows: the block printer only redraws cells that changed, Kitty replaces the
+/// image by `kitty_image_id` (defaulting to a fixed id) and the other protocols print over it.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, Rgba, RgbaImage};
+/// use viuer::{Config, FrameSink};
+///
+/// let mut sink = FrameSink::new(30, &Config::default());
+/// for i in 0..300 {
+///     let img = RgbaImage::from_pixel(40, 40, Rgba([i as u8, 0, 0, 255]));
+///     sink.push(&DynamicImage::ImageRgba8(img))
+///         .expect("Image printing failed.");
+/// }
+/// println!("{:.1} fps", sink.stats().fps);
+/// ```
+#[derive(Debug)]
+pub struct FrameSink {
+    config: Config,
+    printer: PrinterType,
+    renderer: BlockRenderer,
+    // time between two frames, None if unlimited
+    interval: Option<Duration>,
+    // earliest time for the next frame
+    next_due: Option<Instant>,
+    // frames to drop to catch up
+    skip: u32,
+    // rows to move up before the next frame, in relative mode
+    rewind: u32,
+    started: Option<Instant>,
+    frames: u64,
+    dropped: u64,
+    bytes: u64,
+}
+
+/// Statistics about the frames pushed to a [FrameSink].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameStats {
+    /// Number of frames printed.
+    pub frames: u64,
+    /// Number of frames dropped to keep up.
+    pub dropped: u64,
+    /// Frames printed per second since the first frame was pushed.
+    pub fps: f64,
+    /// Bytes written to the terminal per second since the first frame was pushed.
+    pub bytes_per_second: f64,
+}
+
+impl FrameSink {
+    /// Create a sink printing at most `fps` frames per second, or as many as possible if 0.
+    /// Frames are printed according to the config, except for `restore_cursor`.
+    pub fn new(fps: u32, config: &Config) -> Self {
+        let config = Config {
+            kitty_image_id: config.kitty_image_id.or(Some(DEFAULT_KITTY_IMAGE_ID)),
+            restore_cursor: false,
+            ..config.clone()
+        };
+        Self {
+            printer: crate::choose_printer(&config),
+            config,
+            renderer: BlockRenderer::new(),
+            interval: (fps > 0).then(|| Duration::from_secs(1) / fps),
+            next_due: None,
+            skip: 0,
+            rewind: 0,
+            started: None,
+            frames: 0,
+            dropped: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Print the frame once it is its turn, or drop it if printing has fallen behind.
+    /// Returns whether the frame was printed.
+    pub fn push(&mut self, frame: &DynamicImage) -> ViuResult<bool> {
+        self.started.get_or_insert_with(Instant::now);
+        if self.skip > 0 {
+            self.skip -= 1;
+            self.dropped += 1;
+            return Ok(false);
+        }
+        if let Some(due) = self.next_due {
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+
+        let start = Instant::now();
+        // This is required to get a "Term" instance for "::read_key"
+        let term = Term::stdout();
+        let mut stdout = Counter::new(std::io::stdout());
+        let printer = self.printer;
+        self.draw(&printer, &term, &mut stdout, frame)?;
+
+        self.bytes += stdout.bytes;
+        self.printed(start, start.elapsed());
+        Ok(true)
+    }
+
+    /// Statistics about the frames pushed so far.
+    pub fn stats(&self) -> FrameStats {
+        let seconds = self.started.map_or(0.0, |s| s.elapsed().as_secs_f64());
+        let per_second = |count: u64| match seconds > 0.0 {
+            true => count as f64 / seconds,
+            false => 0.0,
+        };
+        FrameStats {
+            frames: self.frames,
+            dropped: self.dropped,
+            fps: per_second(self.frames),
+            bytes_per_second: per_second(self.bytes),
+        }
+    }
+
+    /// Schedule the next frame after one was printed from `start` and took `cost`.
+    fn printed(&mut self, start: Instant, cost: Duration) {
+        self.frames += 1;
+        if let Some(interval) = self.interval {
+            self.next_due = Some(start + interval);
+            self.skip = (cost.as_nanos() / interval.as_nanos()) as u32;
+        }
+    }
+
+    fn draw(
+        &mut self,
+        printer: &PrinterType,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        frame: &DynamicImage,
+    ) -> ViuResult {
+        if let PrinterType::Block = printer {
+            self.renderer
+                .render_to_writecolor(&mut Ansi::new(stdout), frame, &self.config)?;
+            return Ok(());
+        }
+
+        // other printers leave the cursor below the image
+        if self.rewind > 0 {
+            execute!(stdout, MoveToPreviousLine(self.rewind as u16))?;
+        }
+        printer.print(stdin, stdout, frame, &self.config)?;
+        stdout.flush()?;
+
+        if !self.config.absolute_offset {
+            let layout = Layout::new(&orient(frame, &self.config), &self.config)?;
+            let rows = self.config.y as i64 + (layout.box_y + layout.box_height) as i64;
+            self.rewind = std::cmp::max(0, rows) as u32;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the bytes written through it.
+struct Counter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Counter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, bytes: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::read_key::test_utils::TestKeys;
+
+    fn frame(red: u8) -> DynamicImage {
+        let img = image::RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([red, x as u8 * 16, y as u8 * 16, 255])
+        });
+        DynamicImage::ImageRgba8(img)
+    }
+
+    fn draw(sink: &mut FrameSink, printer: PrinterType, frame: &DynamicImage) -> (String, u64) {
+        let mut counter = Counter::new(Vec::new());
+        sink.draw(&printer, &TestKeys::new(&[]), &mut counter, frame)
+            .unwrap();
+        (String::from_utf8(counter.inner).unwrap(), counter.bytes)
+    }
+
+    #[test]
+    fn test_frame_schedule() {
+        let mut sink = FrameSink::new(10, &Config::default());
+        let start = Instant::now();
+
+        sink.printed(start, Duration::from_millis(20));
+        assert_eq!(sink.next_due, Some(start + Duration::from_millis(100)));
+        assert_eq!(sink.skip, 0);
+
+        // too slow for two more frames
+        sink.printed(start, Duration::from_millis(250));
+        assert_eq!(sink.skip, 2);
+        assert!(!sink.push(&frame(0)).unwrap());
+        assert!(!sink.push(&frame(0)).unwrap());
+
+        let stats = sink.stats();
+        assert_eq!((stats.frames, stats.dropped), (2, 2));
+
+        let mut unlimited = FrameSink::new(0, &Config::default());
+        unlimited.printed(start, Duration::from_secs(1));
+        assert_eq!((unlimited.next_due, unlimited.skip), (None, 0));
+    }
+
+    #[test]
+    fn test_frames_block_diff() {
+        let config = Config {
+            truecolor: true,
+            ..Default::default()
+        };
+        let mut sink = FrameSink::new(0, &config);
+
+        let (_, full) = draw(&mut sink, PrinterType::Block, &frame(0));
+        let (output, bytes) = draw(&mut sink, PrinterType::Block, &frame(0));
+        assert_eq!((output.as_str(), bytes), ("", 0));
+
+        // only the changed cell is printed
+        let mut img = frame(0).to_rgba8();
+        img.put_pixel(1, 1, image::Rgba([255, 0, 0, 255]));
+        let (output, bytes) = draw(
+            &mut sink,
+            PrinterType::Block,
+            &DynamicImage::ImageRgba8(img),
+        );
+        assert_eq!(bytes, output.len() as u64);
+        assert!(bytes > 0 && bytes < full / 2);
+    }
+
+    #[test]
+    fn test_frames_relative() {
+        let config = Config {
+            absolute_offset: false,
+            y: 1,
+            ..Default::default()
+        };
+        let mut sink = FrameSink::new(0, &config);
+
+        let (first, _) = draw(&mut sink, PrinterType::iTerm, &frame(0));
+        assert!(first.starts_with("\n\x1b]1337;File=inline=1"));
+        // the image is 8 rows high, below an empty row
+        let (second, _) = draw(&mut sink, PrinterType::iTerm, &frame(0));
+        assert!(second.starts_with("\x1b[9F\n\x1b]1337;File=inline=1"));
+    }
+}
@@ -0,0 +1,428 @@
+use crate::config::{Config, Fit, Rect, Size};
+use crate::error::ViuResult;
+use crate::printer::{Printer, ReadKey};
+use crate::utils::cell_size;
+use console::Term;
+use crossterm::{
+    cursor::{RestorePosition, SavePosition},
+    execute,
+};
+use image::DynamicImage;
+use std::io::Write;
+
+// Tile width used when neither the number of columns nor the width is known
+const DEFAULT_TILE_WIDTH: u32 = 20;
+
+/// Layout of the tiles printed by [print_grid].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    /// Number of tiles per row. Defaults to None, fitting as many tiles as possible
+    /// into the available width.
+    pub columns: Option<u32>,
+    /// Width of a single tile. Percentages refer to the available width. Defaults to None,
+    /// dividing the width evenly between the columns, or 20 cells if there is no column count.
+    pub tile_width: Option<Size>,
+    /// Height of a single tile. Percentages refer to the terminal height. Defaults to None,
+    /// which is half of the tile width and makes tiles roughly square.
+    pub tile_height: Option<Size>,
+    /// Empty cells between two tiles in a row. Defaults to 1.
+    pub column_gap: u32,
+    /// Empty lines between two rows of tiles. Defaults to 1.
+    pub row_gap: u32,
+}
+
+impl std::default::Default for Grid {
+    fn default() -> Self {
+        Self {
+            columns: None,
+            tile_width: None,
+            tile_height: None,
+            column_gap: 1,
+            row_gap: 1,
+        }
+    }
+}
+
+impl Grid {
+    /// Number of columns and size of a tile, in terminal cells.
+    fn tiles(&self, config: &Config) -> (u32, u32, u32) {
+        let (term_w, term_h) = config.terminal_size();
+        let (cell_w, cell_h) = cell_size();
+        let available = match config.width {
+            Some(width) => width.cells(term_w as u32, cell_w as u32),
+            None => std::cmp::max(1, (term_w as u32).saturating_sub(config.x as u32)),
+        };
+
+        let gap = self.column_gap;
+        let tile_width = self.tile_width.map(|w| w.cells(available, cell_w as u32));
+        let (columns, tile_width) = match (self.columns, tile_width) {
+            (Some(columns), Some(width)) => (std::cmp::max(1, columns), width),
+            (Some(columns), None) => {
+                let columns = std::cmp::max(1, columns);
+                let width = available.saturating_sub(gap * (columns - 1)) / columns;
+                (columns, std::cmp::max(1, width))
+            }
+            (None, width) => {
+                let width = width.unwrap_or(DEFAULT_TILE_WIDTH);
+                (std::cmp::max(1, (available + gap) / (width + gap)), width)
+            }
+        };
+
+        let tile_height = match self.tile_height {
+            Some(height) => height.cells((term_h as u32).saturating_sub(1), cell_h as u32),
+            None => std::cmp::max(1, tile_width / 2),
+        };
+        (columns, tile_width, tile_height)
+    }
+
+    /// Size of a tile in pixels, with at least one pixel per column and two per row, like the
+    /// block printer prints them.
+    #[cfg(feature = "print-file")]
+    pub(crate) fn tile_pixels(&self, config: &Config) -> (u32, u32) {
+        let (_, tile_width, tile_height) = self.tiles(config);
+        let (cell_w, cell_h) = cell_size();
+        (
+            tile_width.saturating_mul(std::cmp::max(1, cell_w as u32)),
+            tile_height.saturating_mul(std::cmp::max(2, cell_h as u32)),
+        )
+    }
+}
+
+/// Print the images as tiles of a grid, row by row. Each image is fit into its tile according
+/// to `fit`, with [Fit::Auto] meaning [Fit::Contain], so that every tile takes up the same space.
+///
+/// The grid starts at the `x` and `y` offsets and `width` limits its total width, which
+/// otherwise extends to the right edge of the terminal. Both absolute and relative offsets are
+/// supported. In the latter case, the cursor ends up below the grid.
+///
+/// Returns the position and size of each tile in terminal cells, relative to the offsets.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, RgbaImage};
+/// use viuer::{print_grid, Config, Grid};
+///
+/// let images = vec![DynamicImage::ImageRgba8(RgbaImage::new(40, 40)); 6];
+/// let grid = Grid {
+///     columns: Some(3),
+///     ..Default::default()
+/// };
+/// let conf = Config {
+///     absolute_offset: false,
+///     ..Default::default()
+/// };
+/// let tiles = print_grid(&images, &grid, &conf).expect("Image printing failed.");
+/// assert_eq!(tiles.len(), 6);
+/// ```
+pub fn print_grid(images: &[DynamicImage], grid: &Grid, config: &Config) -> ViuResult<Vec<Rect>> {
+    let mut stdout = std::io::stdout();
+    if config.restore_cursor {
+        execute!(&mut stdout, SavePosition)?;
+    }
+
+    // This is required to get a "Term" instance for "::read_key"
+    let term = Term::stdout();
+
+    let printer = crate::choose_printer(config);
+    let tiles = print_tiles(&printer, &term, &mut stdout, images, grid, config)?;
+
+    if config.restore_cursor {
+        execute!(&mut stdout, RestorePosition)?;
+    };
+
+    Ok(tiles)
+}
+
+pub(crate) fn print_tiles(
+    printer: &impl Printer,
+    stdin: &impl ReadKey,
+    stdout: &mut impl Write,
+    images: &[DynamicImage],
+    grid: &Grid,
+    config: &Config,
+) -> ViuResult<Vec<Rect>> {
+    let (columns, tile_width, tile_height) = grid.tiles(config);
+    let tile_config = Config {
+        width: Some(Size::Cells(tile_width)),
+        height: Some(Size::Cells(tile_height)),
+        fit: match config.fit {
+            Fit::Auto => Fit::Contain,
+            fit => fit,
+        },
+        align: None,
+        restore_cursor: false,
+        ..config.clone()
+    };
+
+    let mut tiles = Vec::with_capacity(images.len());
+    let (mut row_top, mut row_height) = (0, 0);
+    // Printers leave the cursor on the line below the image. In relative mode, this is
+    // the only way to know where the next tile goes.
+    let mut cursor_row: i64 = 0;
+    for (i, img) in images.iter().enumerate() {
+        let column = i as u32 % columns;
+        if column == 0 && i > 0 {
+            row_top += row_height + grid.row_gap;
+            row_height = 0;
+        }
+
+        let x = column * (tile_width + grid.column_gap);
+        let top = config.y as i64 + row_top as i64;
+        let y = match config.absolute_offset {
+            true => top,
+            false => top - cursor_row,
+        };
+        let config = Config {
+            x: config
+                .x
+                .saturating_add(u16::try_from(x).unwrap_or(u16::MAX)),
+            y: y.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+            ..tile_config.clone()
+        };
+
+        let (w, h) = printer.print(stdin, stdout, img, &config)?;
+        tiles.push(Rect::new(x, row_top, w, h));
+        row_height = std::cmp::max(row_height, h);
+        cursor_row = top + h as i64;
+    }
+
+    // move below the tallest tile of the last row
+    let bottom = config.y as i64 + (row_top + row_height) as i64;
+    for _ in cursor_row..bottom {
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::read_key::test_utils::TestKeys;
+    use crate::printer::{iTermPrinter, AnsiBlockPrinter};
+
+    fn images(count: usize) -> Vec<DynamicImage> {
+        vec![DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2)); count]
+    }
+
+    #[test]
+    fn test_grid_tiles() {
+        let config = Config::default();
+        assert_eq!(Grid::default().tiles(&config), (3, 20, 10));
+
+        let grid = Grid {
+            columns: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(grid.tiles(&config), (2, 39, 19));
+
+        let config = Config {
+            width: Some(Size::Cells(21)),
+            ..Default::default()
+        };
+        let grid = Grid {
+            tile_width: Some(Size::Percent(50)),
+            tile_height: Some(Size::Cells(3)),
+            column_gap: 0,
+            ..Default::default()
+        };
+        assert_eq!(grid.tiles(&config), (2, 10, 3));
+    }
+
+    #[test]
+    fn test_print_grid_absolute() {
+        let grid = Grid {
+            columns: Some(2),
+            ..Default::default()
+        };
+        let config = Config {
+            x: 1,
+            y: 2,
+            width: Some(Size::Cells(21)),
+            ..Default::default()
+        };
+        let mut vec = Vec::new();
+
+        let tiles = print_tiles(
+            &iTermPrinter,
+            &TestKeys::new(&[]),
+            &mut vec,
+            &images(3),
+            &grid,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            tiles,
+            vec![
+                Rect::new(0, 0, 10, 5),
+                Rect::new(11, 0, 10, 5),
+                Rect::new(0, 6, 10, 5),
+            ]
+        );
+
+        let result = std::str::from_utf8(&vec).unwrap();
+        assert_eq!(result.matches("\x1b]1337;File=inline=1").count(), 3);
+        for position in ["\x1b[3;2H", "\x1b[3;13H", "\x1b[9;2H"] {
+            assert!(result.contains(position));
+        }
+    }
+
+    #[test]
+    fn test_print_grid_block() {
+        let grid = Grid {
+            columns: Some(2),
+            ..Default::default()
+        };
+        let config = Config {
+            x: 1,
+            y: 2,
+            width: Some(Size::Cells(21)),
+            truecolor: false,
+            ..Default::default()
+        };
+        let mut vec = Vec::new();
+
+        let tiles = print_tiles(
+            &AnsiBlockPrinter,
+            &TestKeys::new(&[]),
+            &mut vec,
+            &images(3),
+            &grid,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            tiles,
+            vec![
+                Rect::new(0, 0, 10, 5),
+                Rect::new(11, 0, 10, 5),
+                Rect::new(0, 6, 10, 5),
+            ]
+        );
+
+        let result = std::str::from_utf8(&vec).unwrap();
+        assert_eq!(
+            result
+                .matches("\u{2584}\u{2580}".repeat(5).as_str())
+                .count(),
+            15
+        );
+        for position in ["\x1b[3;1H\x1b[1C", "\x1b[3;1H\x1b[12C", "\x1b[9;1H\x1b[1C"] {
+            assert!(result.contains(position));
+        }
+
+        // the same tiles in relative mode, where the grid follows the cursor
+        let config = Config {
+            absolute_offset: false,
+            ..config
+        };
+        let mut vec = Vec::new();
+        print_tiles(
+            &AnsiBlockPrinter,
+            &TestKeys::new(&[]),
+            &mut vec,
+            &images(3),
+            &grid,
+            &config,
+        )
+        .unwrap();
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        // down to the offset, back up to the first row for the second tile, then past the gap
+        assert!(result.starts_with("\n\n\x1b[1C"));
+        let second = result.find("\x1b[0m\n\x1b[5F\x1b[12C").unwrap();
+        assert!(result[second..].contains("\x1b[0m\n\n\x1b[1C"));
+        assert!(result.ends_with("\x1b[0m\n"));
+    }
+
+    #[test]
+    fn test_print_grid_saturates() {
+        let print = |grid: &Grid, config: &Config, count| {
+            let mut vec = Vec::new();
+            let tiles = print_tiles(
+                &iTermPrinter,
+                &TestKeys::new(&[]),
+                &mut vec,
+                &images(count),
+                grid,
+                config,
+            )
+            .unwrap();
+            (tiles, String::from_utf8(vec).unwrap())
+        };
+
+        // the third tile is further right than a u16 reaches
+        let grid = Grid {
+            columns: Some(3),
+            tile_width: Some(Size::Cells(40_000)),
+            tile_height: Some(Size::Cells(1)),
+            ..Default::default()
+        };
+        let config = Config {
+            absolute_offset: false,
+            ..Default::default()
+        };
+        let (tiles, result) = print(&grid, &config, 3);
+        assert_eq!(tiles[2].x, 80_002);
+        assert!(result.contains("\x1b[65535C"));
+
+        // the rows below the first one are further down than an i16 reaches
+        let grid = Grid {
+            columns: Some(1),
+            tile_height: Some(Size::Cells(1)),
+            ..Default::default()
+        };
+        let config = Config {
+            y: i16::MAX,
+            ..Default::default()
+        };
+        let (tiles, result) = print(&grid, &config, 3);
+        assert_eq!(tiles[2].y, 4);
+        assert_eq!(result.matches("\x1b[32768;").count(), 3);
+    }
+
+    #[test]
+    fn test_grid_tiles_no_rows() {
+        let config = Config {
+            terminal_size: Some((80, 0)),
+            ..Default::default()
+        };
+        let grid = Grid {
+            tile_height: Some(Size::Percent(50)),
+            ..Default::default()
+        };
+        assert_eq!(grid.tiles(&config), (3, 20, 1));
+    }
+
+    #[test]
+    fn test_print_grid_relative() {
+        let grid = Grid {
+            columns: Some(2),
+            ..Default::default()
+        };
+        let config = Config {
+            absolute_offset: false,
+            width: Some(Size::Cells(21)),
+            ..Default::default()
+        };
+        let mut vec = Vec::new();
+
+        print_tiles(
+            &iTermPrinter,
+            &TestKeys::new(&[]),
+            &mut vec,
+            &images(3),
+            &grid,
+            &config,
+        )
+        .unwrap();
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        // back up to the first row for the second tile, then down past the gap
+        let second = result.find("\x1b[5F\x1b[11C\x1b]1337").unwrap();
+        assert!(result[second..].contains("\x07\n\n\x1b]1337"));
+        assert!(result.ends_with("\x07\n"));
+    }
+}
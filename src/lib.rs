@@ -36,8 +36,8 @@
 //! offset of 4 and horizontal of 10, starting from the top left corner.
 //! ```no_run
 //! let conf = viuer::Config {
-//!     width: Some(40),
-//!     height: Some(30),
+//!     width: Some(viuer::Size::Cells(40)),
+//!     height: Some(viuer::Size::Cells(30)),
 //!     x: 10,
 //!     y: 4,
 //!     ..Default::default()
@@ -48,28 +48,57 @@
 //! ```
 
 #[cfg(feature = "print-file")]
-use std::path::Path;
+use std::{io::Read, path::Path};
 
 use console::Term;
 use crossterm::{
     cursor::{RestorePosition, SavePosition},
     execute,
 };
-use image::DynamicImage;
-use printer::{Printer, PrinterType};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel};
+use printer::{Printer, PrinterType, Source};
 
 mod config;
 mod error;
+mod frames;
+mod grid;
+mod prepared;
 mod printer;
+mod raw;
+mod resize;
+#[cfg(feature = "print-file")]
+mod slideshow;
+mod text;
 mod utils;
+#[cfg(feature = "viewer")]
+mod viewer;
+#[cfg(feature = "print-file")]
+mod watch;
 
-pub use config::Config;
+pub use config::{
+    Align, Backdrop, Border, Caption, CaptionPosition, Config, Filter, Fit, HorizontalAlign,
+    Overflow, Rect, Rotation, Size, Upscale, UpscaleFilter, VerticalAlign,
+};
 pub use error::{ViuError, ViuResult};
-pub use printer::{get_kitty_support, is_iterm_supported, resize, KittySupport};
+pub use frames::{FrameSink, FrameStats};
+pub use grid::{print_grid, Grid};
+pub use prepared::PreparedImage;
+pub use printer::{
+    get_kitty_support, is_iterm_supported, resize, resize_with_config, BlockRenderer, KittySupport,
+};
+pub use raw::{print_raw, PixelFormat};
+pub use resize::{resize_events, ResizeWatcher};
+pub use text::print_with_text;
 pub use utils::terminal_size;
 
 #[cfg(any(feature = "sixel", feature = "icy_sixel"))]
 pub use printer::is_sixel_supported;
+#[cfg(feature = "print-file")]
+pub use slideshow::{image_files, print_index, slideshow};
+#[cfg(feature = "viewer")]
+pub use viewer::Viewer;
+#[cfg(feature = "print-file")]
+pub use watch::watch_file;
 
 /// Default printing method. Uses either iTerm or Kitty graphics protocol, if supported,
 /// and half blocks otherwise.
@@ -112,14 +141,52 @@ pub fn print(img: &DynamicImage, config: &Config) -> ViuResult<(u32, u32)> {
     Ok((w, h))
 }
 
+/// Print any image with 8 bit channels, like an [ImageBuffer], a [SubImage](image::SubImage)
+/// or a [View](image::flat::View) of a raw buffer, see also [print_raw]. Works just like
+/// [print], but does not need a [DynamicImage]: only the part of the image that is printed
+/// is copied, unless it is rotated or flipped.
+///
+/// ## Example
+/// ```no_run
+/// use image::{GenericImageView, Rgb, RgbImage};
+/// use viuer::{print_view, Config};
+///
+/// let img = RgbImage::from_pixel(200, 200, Rgb([0, 196, 0]));
+/// // print the top left corner only
+/// print_view(&*img.view(0, 0, 50, 50), &Config::default()).expect("Image printing failed.");
+/// ```
+pub fn print_view<V, P>(img: &V, config: &Config) -> ViuResult<(u32, u32)>
+where
+    V: GenericImageView<Pixel = P>,
+    P: Pixel<Subpixel = u8>,
+    DynamicImage: From<ImageBuffer<P, Vec<u8>>>,
+{
+    let mut stdout = std::io::stdout();
+    if config.restore_cursor {
+        execute!(&mut stdout, SavePosition)?;
+    }
+
+    // This is required to get a "Term" instance for "::read_key"
+    let term = Term::stdout();
+
+    let source = Source::from_view(img, config)?;
+    let (w, h) = choose_printer(config).print_source(&term, &mut stdout, &source, config)?;
+
+    if config.restore_cursor {
+        execute!(&mut stdout, RestorePosition)?;
+    };
+
+    Ok((w, h))
+}
+
 /// Helper method that reads a file, tries to decode and print it. The feature is available only
 /// with the `print-file` feature.
 ///
 /// ## Example
 /// ```no_run
-/// use viuer::{Config, print_from_file};
+/// use viuer::{Config, Size, print_from_file};
 /// let conf = Config {
-///     width: Some(30),
+///     width: Some(Size::Cells(30)),
 ///     transparent: true,
 ///     ..Default::default()
 /// };
@@ -146,6 +213,54 @@ pub fn print_from_file<P: AsRef<Path>>(filename: P, config: &Config) -> ViuResul
     Ok((w, h))
 }
 
+/// Decode and print an image which is already in memory, like the body of an HTTP response.
+/// The format is guessed from the content. iTerm and Kitty (for PNGs) are given the original
+/// bytes when the image is neither rotated, flipped nor cropped, without decoding them. The
+/// feature is available only with the `print-file` feature.
+///
+/// ## Example
+/// ```no_run
+/// use viuer::{print_from_bytes, Config};
+///
+/// let bytes = std::fs::read("img.png").expect("Could not read the image.");
+/// print_from_bytes(&bytes, &Config::default()).expect("Image printing failed.");
+/// ```
+#[cfg(feature = "print-file")]
+pub fn print_from_bytes(bytes: &[u8], config: &Config) -> ViuResult<(u32, u32)> {
+    let mut stdout = std::io::stdout();
+    if config.restore_cursor {
+        execute!(&mut stdout, SavePosition)?;
+    }
+
+    // This is required to get a "Term" instance for "::read_key"
+    let term = Term::stdout();
+
+    let (w, h) = choose_printer(config).print_from_bytes(&term, &mut stdout, bytes, config)?;
+
+    if config.restore_cursor {
+        execute!(&mut stdout, RestorePosition)?;
+    };
+
+    Ok((w, h))
+}
+
+/// Read an encoded image to the end and print it, see [print_from_bytes]. The feature is
+/// available only with the `print-file` feature.
+///
+/// ## Example
+/// ```no_run
+/// use viuer::{print_from_reader, Config};
+///
+/// // print an image piped to the program
+/// print_from_reader(std::io::stdin(), &Config::default()).expect("Image printing failed.");
+/// ```
+#[cfg(feature = "print-file")]
+pub fn print_from_reader<R: Read>(mut reader: R, config: &Config) -> ViuResult<(u32, u32)> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    print_from_bytes(&bytes, config)
+}
+
 // Choose the appropriate printer to use based on user config and availability
 fn choose_printer(config: &Config) -> PrinterType {
     #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
@@ -0,0 +1,153 @@
+use crate::config::Config;
+use crate::error::ViuResult;
+use crate::printer::{Encoded, PrinterType};
+use crossterm::{
+    cursor::{RestorePosition, SavePosition},
+    execute,
+};
+use image::DynamicImage;
+use std::io::Write;
+
+/// An image which is resized and encoded once, for the printer chosen by the [Config], and
+/// can then be printed many times at different offsets without repeating that work.
+///
+/// Everything but the offsets is fixed when preparing, including the alignment and clipping.
+/// The type is `Send`, so images can be prepared on other threads. With Kitty, the image is
+/// always sent through escape codes, even if the terminal could read it from a file.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, RgbaImage};
+/// use viuer::{Config, PreparedImage, Size};
+///
+/// let img = DynamicImage::ImageRgba8(RgbaImage::new(40, 40));
+/// let conf = Config {
+///     width: Some(Size::Cells(10)),
+///     ..Default::default()
+/// };
+/// let prepared = PreparedImage::new(&img, &conf).expect("Image encoding failed.");
+/// for x in [0, 20, 40] {
+///     prepared.print_at(x, 2).expect("Image printing failed.");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PreparedImage {
+    printer: PrinterType,
+    config: Config,
+    encoded: Encoded,
+}
+
+impl PreparedImage {
+    /// Resize and encode the image according to the config.
+    pub fn new(img: &DynamicImage, config: &Config) -> ViuResult<Self> {
+        Self::with_printer(crate::choose_printer(config), img, config)
+    }
+
+    fn with_printer(printer: PrinterType, img: &DynamicImage, config: &Config) -> ViuResult<Self> {
+        Ok(Self {
+            printer,
+            encoded: printer.encode(img, config)?,
+            config: config.clone(),
+        })
+    }
+
+    /// Print the image at the offsets it was prepared with. Returns the same dimensions as
+    /// [print](crate::print).
+    pub fn print(&self) -> ViuResult<(u32, u32)> {
+        self.print_at(self.config.x, self.config.y)
+    }
+
+    /// Print the image at the given offsets, which are absolute or relative according to
+    /// `absolute_offset`. Returns the same dimensions as [print](crate::print).
+    pub fn print_at(&self, x: u16, y: i16) -> ViuResult<(u32, u32)> {
+        let mut stdout = std::io::stdout();
+        if self.config.restore_cursor {
+            execute!(&mut stdout, SavePosition)?;
+        }
+
+        let size = self.print_to(&mut stdout, x, y)?;
+
+        if self.config.restore_cursor {
+            execute!(&mut stdout, RestorePosition)?;
+        }
+        Ok(size)
+    }
+
+    fn print_to(&self, stdout: &mut impl Write, x: u16, y: i16) -> ViuResult<(u32, u32)> {
+        let config = Config {
+            x,
+            y,
+            ..self.config.clone()
+        };
+        self.printer.write_encoded(stdout, &self.encoded, &config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Border;
+    use crate::printer::iTermPrinter;
+    use crate::printer::read_key::test_utils::TestKeys;
+    use crate::printer::Printer;
+
+    fn image() -> DynamicImage {
+        let img = image::RgbaImage::from_fn(4, 4, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+        DynamicImage::ImageRgba8(img)
+    }
+
+    fn is_send<T: Send>() {}
+
+    #[test]
+    fn test_prepared_is_send() {
+        is_send::<PreparedImage>();
+    }
+
+    #[test]
+    fn test_prepared_matches_print() {
+        let config = Config {
+            x: 3,
+            y: 1,
+            border: Some(Border::Single),
+            ..Default::default()
+        };
+        let mut printed = Vec::new();
+        let size = iTermPrinter
+            .print(&TestKeys::new(&[]), &mut printed, &image(), &config)
+            .unwrap();
+
+        let prepared = PreparedImage::with_printer(PrinterType::iTerm, &image(), &config).unwrap();
+        let mut vec = Vec::new();
+        assert_eq!(prepared.print_to(&mut vec, 3, 1).unwrap(), size);
+        assert_eq!(vec, printed);
+    }
+
+    #[test]
+    fn test_prepared_blocks_at_offsets() {
+        let config = Config {
+            truecolor: true,
+            ..Default::default()
+        };
+        let prepared = PreparedImage::with_printer(PrinterType::Block, &image(), &config).unwrap();
+
+        let mut first = Vec::new();
+        assert_eq!(prepared.print_to(&mut first, 0, 0).unwrap(), (4, 2));
+        let mut second = Vec::new();
+        prepared.print_to(&mut second, 5, 2).unwrap();
+
+        let (first, second) = (
+            String::from_utf8(first).unwrap(),
+            String::from_utf8(second).unwrap(),
+        );
+        let rows: Vec<&str> = first
+            .trim_start_matches("\x1b[1;1H")
+            .split("\r\n")
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            second,
+            format!("\x1b[3;1H\x1b[5C{}\r\n\x1b[5C{}", rows[0], rows[1])
+        );
+        assert!(second.ends_with("\x1b[0m\n"));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::config::Backdrop;
+use image::{imageops::FilterType, Rgb, Rgba, RgbaImage};
+
+/// A [Backdrop] resolved for an image of a specific size, which can be sampled per pixel.
+pub(crate) enum BackdropSampler {
+    Solid(Rgb<u8>),
+    Checkerboard {
+        light: Rgb<u8>,
+        dark: Rgb<u8>,
+        size: u32,
+    },
+    Image(RgbaImage),
+}
+
+impl BackdropSampler {
+    /// Prepare the backdrop for an image of `width`x`height` pixels, where `scale` is the
+    /// number of pixels in one half cell (1 for blocks, see [half_cell] for sixel).
+    pub(crate) fn new(backdrop: &Backdrop, width: u32, height: u32, scale: u32) -> Self {
+        match backdrop {
+            Backdrop::Solid(color) => BackdropSampler::Solid(*color),
+            Backdrop::Checkerboard { light, dark, size } => BackdropSampler::Checkerboard {
+                light: *light,
+                dark: *dark,
+                size: std::cmp::max(1, *size) * std::cmp::max(1, scale),
+            },
+            Backdrop::Image(img) => BackdropSampler::Image(
+                img.resize_exact(
+                    std::cmp::max(1, width),
+                    std::cmp::max(1, height),
+                    FilterType::Triangle,
+                )
+                .to_rgba8(),
+            ),
+        }
+    }
+
+    /// Color of the backdrop at the given pixel.
+    #[inline(always)]
+    pub(crate) fn color_at(&self, x: u32, y: u32) -> Rgb<u8> {
+        match self {
+            BackdropSampler::Solid(color) => *color,
+            BackdropSampler::Checkerboard { light, dark, size } => {
+                if (x / size) % 2 == (y / size) % 2 {
+                    *dark
+                } else {
+                    *light
+                }
+            }
+            BackdropSampler::Image(img) => {
+                let x = std::cmp::min(x, img.width() - 1);
+                let y = std::cmp::min(y, img.height() - 1);
+                let p = img.get_pixel(x, y);
+                Rgb([p[0], p[1], p[2]])
+            }
+        }
+    }
+}
+
+/// Composes the foreground over the background.
+///
+/// This assumes unpremultiplied alpha.
+#[inline(always)]
+fn over(fg: u8, bg: u8, alpha: u8) -> u8 {
+    ((fg as u16 * alpha as u16 + bg as u16 * (255u16 - alpha as u16)) / 255) as _
+}
+
+/// Composes the foreground over the background.
+///
+/// This assumes premultiplied alpha (standard Porter-Duff compositing).
+#[inline(always)]
+fn over_porter_duff(fg: u8, bg: u8, alpha: u8) -> u8 {
+    ((fg as u16 + bg as u16 * (255u16 - alpha as u16)) / 255) as _
+}
+
+/// Blend a pixel with the backdrop color behind it. Opaque pixels are returned as they are.
+#[inline(always)]
+pub(crate) fn blend(color: &Rgba<u8>, bg: Rgb<u8>, premultiplied_alpha: bool) -> Rgb<u8> {
+    let alpha = color[3];
+    if alpha == 255 {
+        return Rgb([color[0], color[1], color[2]]);
+    }
+
+    let compose = if premultiplied_alpha {
+        over_porter_duff
+    } else {
+        over
+    };
+    Rgb([
+        compose(color[0], bg[0], alpha),
+        compose(color[1], bg[1], alpha),
+        compose(color[2], bg[2], alpha),
+    ])
+}
+
+/// Number of screen pixels in one half cell, so that sixel images get a checkerboard of the
+/// same size as the block printer, where every pixel is half a cell.
+#[cfg(any(feature = "sixel", feature = "icy_sixel"))]
+pub(crate) fn half_cell() -> u32 {
+    std::cmp::max(1, crate::utils::cell_size().1 as u32 / 2)
+}
+
+/// Flatten an image over the configured backdrop, making every pixel opaque.
+///
+/// `scale` is the number of pixels in one half cell, see [BackdropSampler::new].
+#[cfg(any(feature = "sixel", feature = "icy_sixel"))]
+pub(crate) fn flatten(img: &RgbaImage, config: &crate::Config, scale: u32) -> RgbaImage {
+    let sampler = BackdropSampler::new(&config.backdrop, img.width(), img.height(), scale);
+    crate::printer::parallel::image_from_fn(img.width(), img.height(), |x, y| {
+        let color = blend(
+            img.get_pixel(x, y),
+            sampler.color_at(x, y),
+            config.premultiplied_alpha,
+        );
+        Rgba([color[0], color[1], color[2], 255])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::DynamicImage;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_default_checkerboard() {
+        let sampler = BackdropSampler::new(&Backdrop::default(), 4, 4, 1);
+        assert_eq!(sampler.color_at(0, 0), Rgb([102, 102, 102]));
+        assert_eq!(sampler.color_at(1, 0), Rgb([153, 153, 153]));
+        assert_eq!(sampler.color_at(1, 1), Rgb([102, 102, 102]));
+    }
+
+    #[cfg(any(feature = "sixel", feature = "icy_sixel"))]
+    #[test]
+    fn test_flatten_half_cell() {
+        // cells are 10x20 pixels in the tests, so the squares are 10 pixels wide
+        assert_eq!(half_cell(), 10);
+        let img = flatten(
+            &RgbaImage::new(20, 20),
+            &crate::Config::default(),
+            half_cell(),
+        );
+        assert_eq!(img.get_pixel(9, 9), &Rgba([102, 102, 102, 255]));
+        assert_eq!(img.get_pixel(10, 9), &Rgba([153, 153, 153, 255]));
+        assert_eq!(img.get_pixel(10, 10), &Rgba([102, 102, 102, 255]));
+    }
+
+    #[test]
+    fn test_scaled_checkerboard() {
+        let backdrop = Backdrop::Checkerboard {
+            light: Rgb([255, 255, 255]),
+            dark: Rgb([0, 0, 0]),
+            size: 2,
+        };
+        let sampler = BackdropSampler::new(&backdrop, 24, 24, 6);
+        assert_eq!(sampler.color_at(11, 11), Rgb([0, 0, 0]));
+        assert_eq!(sampler.color_at(12, 11), Rgb([255, 255, 255]));
+        assert_eq!(sampler.color_at(12, 12), Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_image_backdrop() {
+        let bg = image::RgbImage::from_pixel(1, 1, Rgb([10, 20, 30]));
+        let backdrop = Backdrop::Image(Arc::new(DynamicImage::ImageRgb8(bg)));
+        let sampler = BackdropSampler::new(&backdrop, 3, 2, 1);
+        assert_eq!(sampler.color_at(2, 1), Rgb([10, 20, 30]));
+    }
+
+    #[test]
+    fn test_blend() {
+        let bg = Rgb([100, 100, 100]);
+        assert_eq!(blend(&Rgba([1, 2, 3, 255]), bg, false), Rgb([1, 2, 3]));
+        assert_eq!(blend(&Rgba([200, 0, 100, 0]), bg, false), bg);
+        assert_eq!(
+            blend(&Rgba([200, 0, 100, 51]), bg, false),
+            Rgb([120, 80, 100])
+        );
+    }
+}
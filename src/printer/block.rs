@@ -1,233 +1,627 @@
 use crate::error::ViuResult;
-use crate::printer::{adjust_offset, Printer, ReadKey};
+use crate::printer::backdrop::{blend, BackdropSampler};
+use crate::printer::decoration::Decoration;
+use crate::printer::layout::Layout;
+use crate::printer::parallel::{for_each_row, rgba8};
+use crate::printer::{adjust_offset, cursor_position, Encoded, Printer, ReadKey, Source};
 use crate::Config;
 
 use ansi_colours::ansi256_from_rgb;
-use image::{DynamicImage, GenericImageView, Rgba};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, Pixel, Rgb, Rgba};
 use std::io::Write;
-use termcolor::{BufferedStandardStream, Color, ColorChoice, ColorSpec, WriteColor};
+use termcolor::{Ansi, BufferedStandardStream, Color, ColorChoice, ColorSpec, WriteColor};
 
-use crossterm::cursor::MoveRight;
+use crossterm::cursor::{
+    MoveRight, MoveToColumn, MoveToNextLine, MoveToPreviousLine, RestorePosition, SavePosition,
+};
 use crossterm::execute;
 
 const UPPER_HALF_BLOCK: &str = "\u{2580}";
 const LOWER_HALF_BLOCK: &str = "\u{2584}";
 
-const CHECKERBOARD_BACKGROUND_LIGHT: (u8, u8, u8) = (153, 153, 153);
-const CHECKERBOARD_BACKGROUND_DARK: (u8, u8, u8) = (102, 102, 102);
-
 #[derive(Debug)]
 pub struct BlockPrinter;
 
 impl Printer for BlockPrinter {
-    fn print(
+    fn print_source(
         &self,
         _stdin: &impl ReadKey,
         // TODO: The provided object is not used because termcolor needs an implementation of the WriteColor trait
         _stdout: &mut impl Write,
-        img: &DynamicImage,
+        source: &Source,
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
         let mut stream = BufferedStandardStream::stdout(ColorChoice::Always);
-        print_to_writecolor(&mut stream, img, config)
+        print_to_writecolor(&mut stream, source, config)
+    }
+}
+
+/// The block printer, writing to the given `stdout` instead of termcolor's standard stream, so
+/// that tests can check its output.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct AnsiBlockPrinter;
+
+#[cfg(test)]
+impl Printer for AnsiBlockPrinter {
+    fn print_source(
+        &self,
+        _stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        source: &Source,
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        print_to_writecolor(&mut Ansi::new(stdout), source, config)
     }
 }
 
 fn print_to_writecolor(
     stdout: &mut impl WriteColor,
-    img: &DynamicImage,
+    source: &Source,
     config: &Config,
 ) -> ViuResult<(u32, u32)> {
-    // adjust with x=0 and handle horizontal offset entirely below
-    adjust_offset(stdout, &Config { x: 0, ..*config })?;
+    let layout = source.layout;
+    let grid = CellGrid::new(&source.img, &layout, config);
+    let placed = layout.start(stdout, config)?;
+    write_grid(stdout, &grid, &placed)?;
+    layout.finish(stdout)?;
+    stdout.flush()?;
 
-    // resize the image so that it fits in the constraints, if any
-    let img = super::resize(img, config.width, config.height);
-    let (width, height) = img.dimensions();
+    Ok((layout.box_width, layout.box_height))
+}
 
-    let mut row_color_buffer: Vec<ColorSpec> = vec![ColorSpec::new(); width as usize];
-    let img_buffer = img.to_rgba8(); //TODO: Can conversion be avoided?
+/// A terminal cell, made of two vertically stacked pixels. `None` means transparent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    top: Option<Color>,
+    bottom: Option<Color>,
+}
 
-    for (curr_row, img_row) in img_buffer.enumerate_rows() {
-        let is_even_row = curr_row % 2 == 0;
-        let is_last_row = curr_row == height - 1;
+/// The colors of every cell needed to print an image, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CellGrid {
+    // dimensions in terminal cells
+    width: u32,
+    height: u32,
+    // true when the bottom half of the last row is not part of the image
+    odd_height: bool,
+    cells: Vec<Cell>,
+}
 
-        // move right if x offset is specified
-        if config.x > 0 && (!is_even_row || is_last_row) {
-            execute!(stdout, MoveRight(config.x))?;
+impl CellGrid {
+    fn new(img: &DynamicImage, layout: &Layout, config: &Config) -> Self {
+        // crop and resize the image so that it fits in the constraints, if any
+        // Each cell holds two pixels, but an image with odd height only covers the top half
+        // of the last row.
+        let (_, _, _, crop_height) = layout.crop;
+        let (width, height) = (layout.width, 2 * layout.height - crop_height % 2);
+        let img = layout.resize(img, width, height, config, FilterType::CatmullRom);
+        let (width, height) = img.dimensions();
+        let rows = height / 2 + height % 2;
+        let mut cells = vec![
+            Cell {
+                top: None,
+                bottom: None
+            };
+            (width * rows) as usize
+        ];
+        // RGB and RGBA images are read as they are, without converting them
+        match &img {
+            DynamicImage::ImageRgb8(rgb) => fill_cells(
+                &mut cells,
+                &img,
+                |x, y| rgb.get_pixel(x, y).to_rgba(),
+                config,
+            ),
+            _ => {
+                let rgba = rgba8(&img);
+                fill_cells(&mut cells, &img, |x, y| *rgba.get_pixel(x, y), config)
+            }
         }
 
-        for pixel in img_row {
-            // choose the half block's color
-            let color = if is_pixel_transparent(pixel) {
-                if config.transparent {
-                    None
-                } else {
-                    Some(transparency_color(curr_row, pixel.0, config.truecolor))
-                }
-            } else {
-                Some(color_from_pixel(curr_row, pixel, config))
-            };
+        Self {
+            width,
+            height: rows,
+            odd_height: height % 2 == 1,
+            cells,
+        }
+    }
 
-            // Even rows modify the background, odd rows the foreground
-            // because lower half blocks are used by default
-            let colorspec = &mut row_color_buffer[pixel.0 as usize];
-            if is_even_row {
-                colorspec.set_bg(color);
-                if is_last_row {
-                    write_colored_character(stdout, colorspec, true)?;
-                }
+    fn row(&self, row: u32) -> &[Cell] {
+        let start = (row * self.width) as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+
+    fn is_last_odd_row(&self, row: u32) -> bool {
+        self.odd_height && row == self.height - 1
+    }
+}
+
+/// Choose the colors of the cells covering an image, given its pixels.
+fn fill_cells(
+    cells: &mut [Cell],
+    img: &DynamicImage,
+    pixel_at: impl Fn(u32, u32) -> Rgba<u8> + Send + Sync,
+    config: &Config,
+) {
+    let (width, height) = img.dimensions();
+    let backdrop = BackdropSampler::new(&config.backdrop, width, height, 1);
+
+    // choose the half block's color
+    let color_at = |x: u32, y: u32| {
+        let color = pixel_at(x, y);
+        let pixel = (x, y, &color);
+        if is_pixel_transparent(pixel) {
+            if config.transparent {
+                None
             } else {
-                colorspec.set_fg(color);
-                write_colored_character(stdout, colorspec, false)?;
+                Some(to_color(backdrop.color_at(x, y), config))
             }
+        } else {
+            Some(color_from_pixel(y, pixel, &backdrop, config))
         }
+    };
 
-        if !is_even_row && !is_last_row {
-            stdout.reset()?;
-            writeln!(stdout, "\r")?;
+    // each row of cells covers two rows of pixels
+    for_each_row(cells, width as usize, |row, cells| {
+        let y = 2 * row as u32;
+        for (x, cell) in (0..).zip(cells.iter_mut()) {
+            cell.top = color_at(x, y);
+            if y + 1 < height {
+                cell.bottom = color_at(x, y + 1);
+            }
+        }
+    });
+}
+
+/// Print the whole grid, starting from the offset in the config.
+fn write_grid(stdout: &mut impl WriteColor, grid: &CellGrid, config: &Config) -> ViuResult {
+    // adjust with x=0 and handle horizontal offset entirely below
+    adjust_offset(
+        stdout,
+        &Config {
+            x: 0,
+            ..config.clone()
+        },
+    )?;
+
+    let mut writer = CellWriter::new(stdout);
+    for row in 0..grid.height {
+        // move right if x offset is specified
+        writer.skip(config.x);
+
+        let is_last_row = grid.is_last_odd_row(row);
+        for cell in grid.row(row) {
+            writer.write_cell(cell, is_last_row)?;
+        }
+        writer.end_row()?;
+
+        if row != grid.height - 1 {
+            writeln!(writer.stdout, "\r")?;
+        }
+    }
+
+    writeln!(stdout)?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Encode the image for [write_encoded], as one line of colored cells per row.
+pub(crate) fn encode(source: &Source, config: &Config) -> ViuResult<Encoded> {
+    let layout = source.layout;
+    let grid = CellGrid::new(&source.img, &layout, config);
+
+    let mut data = Vec::with_capacity(grid.height as usize);
+    for row in 0..grid.height {
+        let mut line = Ansi::new(Vec::new());
+        let mut writer = CellWriter::new(&mut line);
+        let is_last_row = grid.is_last_odd_row(row);
+        for cell in grid.row(row) {
+            writer.write_cell(cell, is_last_row)?;
         }
+        writer.end_row()?;
+        data.push(line.into_inner());
     }
+    Ok(Encoded { layout, data })
+}
 
-    stdout.reset()?;
+/// Print an image encoded by [encode]. Unlike the block printer, this writes to `stdout`
+/// instead of going through termcolor's standard stream.
+pub(crate) fn write_encoded(
+    stdout: &mut impl Write,
+    encoded: &Encoded,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let layout = &encoded.layout;
+    let placed = layout.start(stdout, config)?;
+    adjust_offset(
+        stdout,
+        &Config {
+            x: 0,
+            ..placed.clone()
+        },
+    )?;
+
+    for (row, line) in encoded.data.iter().enumerate() {
+        // Some terminals interpret 0 as 1, see MoveRight documentation
+        if placed.x > 0 {
+            execute!(stdout, MoveRight(placed.x))?;
+        }
+        stdout.write_all(line)?;
+        if row != encoded.data.len() - 1 {
+            writeln!(stdout, "\r")?;
+        }
+    }
     writeln!(stdout)?;
+    layout.finish(stdout)?;
     stdout.flush()?;
 
-    Ok((width, height / 2 + height % 2))
+    Ok((layout.box_width, layout.box_height))
+}
+
+/// Block printer which keeps the previously printed frame around and, on subsequent calls,
+/// only redraws the cells that changed. This greatly reduces the amount of data sent to the
+/// terminal when an image is updated in place, e.g. for animations.
+///
+/// The whole image is printed again if its size, position or decoration changes, or after
+/// [BlockRenderer::reset]. If the new image is smaller, the leftovers of the previous one are
+/// not cleared.
+///
+/// In relative mode (`absolute_offset: false`) the cursor is expected to stay where the
+/// previous render left it, unless `restore_cursor` is set.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, RgbaImage, Rgba};
+/// use viuer::{BlockRenderer, Config};
+///
+/// let conf = Config::default();
+/// let mut renderer = BlockRenderer::new();
+///
+/// let mut img = RgbaImage::new(40, 40);
+/// for i in 0..40 {
+///     img.put_pixel(i, i, Rgba([255, 0, 0, 255]));
+///     // only the cell containing the new pixel is sent to the terminal
+///     renderer
+///         .render(&DynamicImage::ImageRgba8(img.clone()), &conf)
+///         .expect("Image printing failed.");
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct BlockRenderer {
+    previous: Option<Frame>,
 }
 
-fn write_colored_character(
+#[derive(Debug)]
+struct Frame {
+    grid: CellGrid,
+    x: u16,
+    y: i16,
+    absolute_offset: bool,
+    // empty rows left below the image
+    padding: u32,
+    decoration: Decoration,
+}
+
+impl BlockRenderer {
+    /// Create a renderer without a previous frame.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget the previous frame, so that the next render prints the whole image.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Print the image to stdout, redrawing only what changed since the last call.
+    /// Returns the dimensions of the image in terminal cells, just like [crate::print].
+    pub fn render(&mut self, img: &DynamicImage, config: &Config) -> ViuResult<(u32, u32)> {
+        let mut stream = BufferedStandardStream::stdout(ColorChoice::Always);
+        if config.restore_cursor {
+            execute!(&mut stream, SavePosition)?;
+        }
+
+        let result = self.render_to_writecolor(&mut stream, img, config)?;
+
+        if config.restore_cursor {
+            execute!(&mut stream, RestorePosition)?;
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn render_to_writecolor(
+        &mut self,
+        stdout: &mut impl WriteColor,
+        img: &DynamicImage,
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        let Source { img, layout } = Source::new(img, config)?;
+        let grid = CellGrid::new(&img, &layout, config);
+        let placed = layout.place(config);
+        let padding = layout.bottom_padding();
+        let decoration = Decoration::new(config);
+
+        match &self.previous {
+            Some(prev)
+                if prev.grid.width == grid.width
+                    && prev.grid.height == grid.height
+                    && prev.grid.odd_height == grid.odd_height
+                    && prev.x == placed.x
+                    && prev.y == placed.y
+                    && prev.absolute_offset == placed.absolute_offset
+                    && prev.padding == padding
+                    && prev.decoration == decoration =>
+            {
+                write_diff(stdout, &prev.grid, &grid, &placed, padding)?;
+            }
+            _ => {
+                let start = layout.start(stdout, config)?;
+                write_grid(stdout, &grid, &start)?;
+                layout.finish(stdout)?;
+                stdout.flush()?;
+            }
+        }
+
+        self.previous = Some(Frame {
+            grid,
+            x: placed.x,
+            y: placed.y,
+            absolute_offset: placed.absolute_offset,
+            padding,
+            decoration,
+        });
+        Ok((layout.box_width, layout.box_height))
+    }
+}
+
+/// Print only the cells of `grid` that differ from `prev`. Both must have the same size.
+///
+/// The cursor is left where a full print would leave it, `padding` lines after the image.
+fn write_diff(
     stdout: &mut impl WriteColor,
-    c: &ColorSpec,
-    is_last_row: bool,
+    prev: &CellGrid,
+    grid: &CellGrid,
+    config: &Config,
+    padding: u32,
 ) -> ViuResult {
-    let out_color;
-    let out_char;
-    let mut new_color;
-
-    // On the last row use upper blocks and leave the bottom half empty (transparent)
-    if is_last_row {
-        new_color = ColorSpec::new();
-        if let Some(bg) = c.bg() {
-            new_color.set_fg(Some(*bg));
-            out_char = UPPER_HALF_BLOCK;
-        } else {
-            execute!(stdout, MoveRight(1))?;
-            return Ok(());
-        }
-        out_color = &new_color;
+    let changed = |row: u32, col: u32| {
+        let idx = (row * grid.width + col) as usize;
+        prev.cells[idx] != grid.cells[idx]
+    };
+
+    let Some(first_row) = (0..grid.height).find(|&r| (0..grid.width).any(|c| changed(r, c))) else {
+        // nothing to do, the cursor stays where it is
+        return Ok(());
+    };
+
+    // go to the first column of the image's first row
+    if config.absolute_offset || config.restore_cursor {
+        adjust_offset(
+            stdout,
+            &Config {
+                x: 0,
+                ..config.clone()
+            },
+        )?;
     } else {
-        match (c.fg(), c.bg()) {
-            (None, None) => {
-                // completely transparent
-                execute!(stdout, MoveRight(1))?;
-                return Ok(());
-            }
-            (Some(bottom), None) => {
-                // only top transparent
-                new_color = ColorSpec::new();
-                new_color.set_fg(Some(*bottom));
-                out_color = &new_color;
-                out_char = LOWER_HALF_BLOCK;
+        execute!(stdout, MoveToPreviousLine((grid.height + padding) as u16))?;
+    }
+
+    let mut writer = CellWriter::new(stdout);
+    let mut curr_row = 0;
+    for row in first_row..grid.height {
+        let is_last_row = grid.is_last_odd_row(row);
+        let cells = grid.row(row);
+        let mut col = 0;
+
+        while col < grid.width {
+            if !changed(row, col) {
+                col += 1;
+                continue;
             }
-            (None, Some(top)) => {
-                // only bottom transparent
-                new_color = ColorSpec::new();
-                new_color.set_fg(Some(*top));
-                out_color = &new_color;
-                out_char = UPPER_HALF_BLOCK;
+
+            if row > curr_row {
+                execute!(writer.stdout, MoveToNextLine((row - curr_row) as u16))?;
+                curr_row = row;
             }
-            (Some(_top), Some(_bottom)) => {
-                // both parts have a color
-                out_color = c;
-                out_char = LOWER_HALF_BLOCK;
+            writer.discard_skips();
+            let x = config
+                .x
+                .saturating_add(u16::try_from(col).unwrap_or(u16::MAX));
+            execute!(writer.stdout, MoveToColumn(cursor_position(x)))?;
+
+            // write the whole run of changed cells
+            while col < grid.width && changed(row, col) {
+                let cell = &cells[col as usize];
+                if cell.top.is_none() && (cell.bottom.is_none() || is_last_row) {
+                    // a transparent cell would just move the cursor, erase what was there
+                    writer.erase()?;
+                } else {
+                    writer.write_cell(cell, is_last_row)?;
+                }
+                col += 1;
             }
         }
     }
-    stdout.set_color(out_color)?;
-    write!(stdout, "{}", out_char)?;
+    writer.end_row()?;
+
+    if !config.restore_cursor {
+        execute!(
+            stdout,
+            MoveToNextLine((grid.height + padding - curr_row) as u16)
+        )?;
+    }
+    stdout.flush()?;
 
     Ok(())
 }
 
-fn is_pixel_transparent(pixel: (u32, u32, &Rgba<u8>)) -> bool {
-    pixel.2[3] == 0
+/// Writes cells while keeping track of the colors currently set in the terminal, so that SGR
+/// sequences are only emitted when something changes. Runs of transparent cells are skipped
+/// with a single cursor movement.
+struct CellWriter<'a, W: WriteColor> {
+    stdout: &'a mut W,
+    // false until the first reset, as the colors set before printing are unknown
+    known: bool,
+    fg: Option<Color>,
+    bg: Option<Color>,
+    pending_skips: u16,
 }
 
-#[inline(always)]
-fn checkerboard(row: u32, col: u32) -> (u8, u8, u8) {
-    if row % 2 == col % 2 {
-        CHECKERBOARD_BACKGROUND_DARK
-    } else {
-        CHECKERBOARD_BACKGROUND_LIGHT
+impl<'a, W: WriteColor> CellWriter<'a, W> {
+    fn new(stdout: &'a mut W) -> Self {
+        Self {
+            stdout,
+            known: false,
+            fg: None,
+            bg: None,
+            pending_skips: 0,
+        }
     }
-}
 
-#[inline(always)]
-fn transparency_color(row: u32, col: u32, truecolor: bool) -> Color {
-    //imitate the transparent chess board pattern
-    let rgb = checkerboard(row, col);
-    if truecolor {
-        Color::Rgb(rgb.0, rgb.1, rgb.2)
-    } else {
-        Color::Ansi256(ansi256_from_rgb(rgb))
+    /// Move the cursor right before the next written cell.
+    fn skip(&mut self, cells: u16) {
+        self.pending_skips += cells;
+    }
+
+    /// Forget about pending skips, e.g. because the cursor is moved explicitly.
+    fn discard_skips(&mut self) {
+        self.pending_skips = 0;
+    }
+
+    fn write_cell(&mut self, cell: &Cell, is_last_row: bool) -> ViuResult {
+        // On the last row leave the bottom half empty (transparent)
+        let bottom = if is_last_row { None } else { cell.bottom };
+
+        let (out_char, fg, bg) = match (cell.top, bottom) {
+            (None, None) => {
+                // completely transparent
+                self.skip(1);
+                return Ok(());
+            }
+            // only bottom transparent
+            (Some(top), None) => (UPPER_HALF_BLOCK, Some(top), None),
+            // only top transparent
+            (None, Some(bottom)) => (LOWER_HALF_BLOCK, Some(bottom), None),
+            // a single color, the foreground does not matter
+            (Some(top), Some(bottom)) if top == bottom => (" ", None, Some(top)),
+            (Some(top), Some(bottom)) => {
+                // both parts have a color, pick the block which needs fewer changes
+                let lower_cost = (self.fg != Some(bottom)) as u8 + (self.bg != Some(top)) as u8;
+                let upper_cost = (self.fg != Some(top)) as u8 + (self.bg != Some(bottom)) as u8;
+                if upper_cost < lower_cost {
+                    (UPPER_HALF_BLOCK, Some(top), Some(bottom))
+                } else {
+                    (LOWER_HALF_BLOCK, Some(bottom), Some(top))
+                }
+            }
+        };
+
+        self.write_char(out_char, fg, bg)
+    }
+
+    /// Clear the cell under the cursor.
+    fn erase(&mut self) -> ViuResult {
+        self.write_char(" ", None, None)
+    }
+
+    /// Drop pending skips and restore the default colors.
+    fn end_row(&mut self) -> ViuResult {
+        self.discard_skips();
+        if self.fg.is_some() || self.bg.is_some() {
+            self.stdout.reset()?;
+            self.fg = None;
+            self.bg = None;
+        }
+        Ok(())
+    }
+
+    // A `None` foreground means that any color will do
+    fn write_char(&mut self, c: &str, fg: Option<Color>, bg: Option<Color>) -> ViuResult {
+        if self.pending_skips > 0 {
+            execute!(self.stdout, MoveRight(self.pending_skips))?;
+            self.pending_skips = 0;
+        }
+
+        // there is no way to unset a single color, so start over
+        if !self.known || (bg.is_none() && self.bg.is_some()) {
+            self.stdout.reset()?;
+            self.known = true;
+            self.fg = None;
+            self.bg = None;
+        }
+
+        let mut spec = ColorSpec::new();
+        spec.set_reset(false);
+        if fg.is_some() && fg != self.fg {
+            spec.set_fg(fg);
+            self.fg = fg;
+        }
+        if bg != self.bg {
+            spec.set_bg(bg);
+            self.bg = bg;
+        }
+        if !spec.is_none() {
+            self.stdout.set_color(&spec)?;
+        }
+
+        write!(self.stdout, "{}", c)?;
+        Ok(())
     }
 }
 
-/// Composes the foreground over the background.
-///
-/// This assumes unpremultiplied alpha.
-#[inline(always)]
-fn over(fg: u8, bg: u8, alpha: u8) -> u8 {
-    ((fg as u16 * alpha as u16 + bg as u16 * (255u16 - alpha as u16)) / 255) as _
+fn is_pixel_transparent(pixel: (u32, u32, &Rgba<u8>)) -> bool {
+    pixel.2[3] == 0
 }
 
-/// Composes the foreground over the background.
-///
-/// This assumes premultiplied alpha (standard Porter-Duff compositing).
 #[inline(always)]
-fn over_porter_duff(fg: u8, bg: u8, alpha: u8) -> u8 {
-    ((fg as u16 + bg as u16 * (255u16 - alpha as u16)) / 255) as _
+fn to_color(rgb: Rgb<u8>, config: &Config) -> Color {
+    if config.truecolor {
+        Color::Rgb(rgb[0], rgb[1], rgb[2])
+    } else {
+        Color::Ansi256(ansi256_from_rgb((rgb[0], rgb[1], rgb[2])))
+    }
 }
 
 #[inline(always)]
-fn color_from_pixel(row: u32, pixel: (u32, u32, &Rgba<u8>), config: &Config) -> Color {
+fn color_from_pixel(
+    row: u32,
+    pixel: (u32, u32, &Rgba<u8>),
+    backdrop: &BackdropSampler,
+    config: &Config,
+) -> Color {
     let (col, _y, color) = pixel;
-    let alpha = color[3];
-
-    let rgb = if !config.transparent && alpha < 255 {
-        // We need to blend the pixel's color with the checkerboard pattern.
-        let checker = checkerboard(row, col);
-
-        if config.premultiplied_alpha {
-            (
-                over_porter_duff(color[0], checker.0, alpha),
-                over_porter_duff(color[1], checker.1, alpha),
-                over_porter_duff(color[2], checker.2, alpha),
-            )
-        } else {
-            (
-                over(color[0], checker.0, alpha),
-                over(color[1], checker.1, alpha),
-                over(color[2], checker.2, alpha),
-            )
-        }
+
+    let rgb = if config.transparent {
+        Rgb([color[0], color[1], color[2]])
     } else {
-        (color[0], color[1], color[2])
+        // We need to blend the pixel's color with the backdrop.
+        blend(
+            color,
+            backdrop.color_at(col, row),
+            config.premultiplied_alpha,
+        )
     };
 
-    if config.truecolor {
-        Color::Rgb(rgb.0, rgb.1, rgb.2)
-    } else {
-        Color::Ansi256(ansi256_from_rgb(rgb))
-    }
+    to_color(rgb, config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Size;
     use termcolor::{Ansi, Color};
 
+    fn print_image(
+        stdout: &mut impl WriteColor,
+        img: &DynamicImage,
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        print_to_writecolor(stdout, &Source::new(img, config)?, config)
+    }
+
     // Note: truecolor is not supported in CI. Hence, it should be disabled when writing the tests
 
     #[test]
@@ -240,12 +634,12 @@ mod tests {
             ..Default::default()
         };
 
-        let (w, h) = print_to_writecolor(&mut buf, &img, &config).unwrap();
+        let (w, h) = print_image(&mut buf, &img, &config).unwrap();
         assert_eq!((w, h), (5, 2));
 
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[1;1H\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\x1b[38;5;241m\x1b[48;5;247m▄\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\x1b[38;5;241m\x1b[48;5;247m▄\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\r\n\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\x1b[38;5;241m\x1b[48;5;247m▄\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\x1b[38;5;241m\x1b[48;5;247m▄\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\n"
+            "\x1b[1;1H\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄▀▄▀▄\x1b[0m\r\n\x1b[38;5;247m\x1b[48;5;241m▄▀▄▀▄\x1b[0m\n"
         );
     }
 
@@ -259,115 +653,400 @@ mod tests {
             ..Default::default()
         };
 
-        let (w, h) = print_to_writecolor(&mut buf, &img, &config).unwrap();
+        let (w, h) = print_image(&mut buf, &img, &config).unwrap();
         assert_eq!((w, h), (5, 2));
 
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[1;1H\x1b[1C\x1b[1C\x1b[1C\x1b[1C\x1b[1C\x1b[0m\r\n\x1b[1C\x1b[1C\x1b[1C\x1b[1C\x1b[1C\x1b[0m\n"
+            "\x1b[1;1H\r\n\n"
         );
     }
 
     #[test]
-    fn test_block_printer_e2e_odd_height() {
-        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 3));
+    fn test_block_printer_e2e_solid_backdrop() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+        let mut buf = Ansi::new(vec![]);
+
+        let config = Config {
+            truecolor: true,
+            backdrop: crate::Backdrop::Solid(Rgb([1, 2, 3])),
+            ..Default::default()
+        };
+
+        let (w, h) = print_image(&mut buf, &img, &config).unwrap();
+        assert_eq!((w, h), (2, 1));
+
+        assert_eq!(
+            std::str::from_utf8(buf.get_ref()).unwrap(),
+            "\x1b[1;1H\x1b[0m\x1b[48;2;1;2;3m  \x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_block_printer_e2e_contain() {
+        let img =
+            DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 2, Rgba([0, 0, 255, 255])));
         let mut buf = Ansi::new(vec![]);
 
         let config = Config {
             truecolor: false,
             absolute_offset: false,
+            width: Some(Size::Cells(3)),
+            height: Some(Size::Cells(3)),
+            fit: crate::Fit::Contain,
             ..Default::default()
         };
-        let (w, h) = print_to_writecolor(&mut buf, &img, &config).unwrap();
-        assert_eq!((w, h), (4, 2));
+
+        // upscaled to 3x6 pixels, in the middle of a 3x3 box
+        let (w, h) = print_image(&mut buf, &img, &config).unwrap();
+        assert_eq!((w, h), (3, 3));
 
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\x1b[38;5;241m\x1b[48;5;247m▄\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄\x1b[0m\x1b[38;5;241m\x1b[48;5;247m▄\x1b[0m\r\n\x1b[0m\x1b[38;5;241m▀\x1b[0m\x1b[38;5;247m▀\x1b[0m\x1b[38;5;241m▀\x1b[0m\x1b[38;5;247m▀\x1b[0m\n"
+            "\x1b[0m\x1b[48;5;21m   \x1b[0m\r\n\x1b[48;5;21m   \x1b[0m\r\n\x1b[48;5;21m   \x1b[0m\n"
+        );
+
+        let config = Config {
+            fit: crate::Fit::None,
+            ..config
+        };
+        let mut buf = Ansi::new(vec![]);
+        let (w, h) = print_image(&mut buf, &img, &config).unwrap();
+        assert_eq!((w, h), (3, 3));
+
+        // one row down and one column right, then one line for the bottom padding
+        assert_eq!(
+            std::str::from_utf8(buf.get_ref()).unwrap(),
+            "\n\x1b[1C\x1b[0m\x1b[48;5;21m \x1b[0m\n\n"
         );
     }
 
     #[test]
-    fn test_write_colored_char_only_fg() {
+    fn test_block_printer_e2e_integer_upscale() {
+        let mut img = image::RgbaImage::new(1, 2);
+        img.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        img.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
         let mut buf = Ansi::new(vec![]);
-        let mut c = ColorSpec::new();
 
-        c.set_fg(Some(Color::Rgb(10, 20, 30)));
+        let config = Config {
+            truecolor: false,
+            absolute_offset: false,
+            width: Some(Size::Cells(2)),
+            upscale: crate::Upscale::Integer,
+            upscale_filter: crate::UpscaleFilter::Nearest,
+            ..Default::default()
+        };
+
+        // each pixel becomes a 2x2 square, without any blending
+        let (w, h) = print_image(&mut buf, &DynamicImage::ImageRgba8(img), &config).unwrap();
+        assert_eq!((w, h), (2, 2));
 
-        write_colored_character(&mut buf, &c, false).unwrap();
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[0m\x1b[38;2;10;20;30m▄"
+            "\x1b[0m\x1b[48;5;196m  \x1b[0m\r\n\x1b[48;5;21m  \x1b[0m\n"
         );
     }
 
     #[test]
-    fn test_write_colored_char_only_bg() {
+    fn test_block_printer_e2e_odd_height() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 3));
         let mut buf = Ansi::new(vec![]);
-        let mut c = ColorSpec::new();
 
-        c.set_bg(Some(Color::Rgb(50, 60, 70)));
+        let config = Config {
+            truecolor: false,
+            absolute_offset: false,
+            ..Default::default()
+        };
+        let (w, h) = print_image(&mut buf, &img, &config).unwrap();
+        assert_eq!((w, h), (4, 2));
 
-        write_colored_character(&mut buf, &c, false).unwrap();
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[0m\x1b[38;2;50;60;70m▀"
+            "\x1b[0m\x1b[38;5;247m\x1b[48;5;241m▄▀▄▀\x1b[0m\r\n\x1b[38;5;241m▀\x1b[38;5;247m▀\x1b[38;5;241m▀\x1b[38;5;247m▀\x1b[0m\n"
         );
     }
 
     #[test]
-    fn test_write_colored_char_fg_and_bg() {
+    fn test_block_renderer_redraws_changed_cells() {
+        let mut img = image::RgbaImage::new(4, 4);
+        let mut renderer = BlockRenderer::new();
+
+        let config = Config {
+            truecolor: false,
+            ..Default::default()
+        };
+
         let mut buf = Ansi::new(vec![]);
-        let mut c = ColorSpec::new();
+        let first = DynamicImage::ImageRgba8(img.clone());
+        renderer
+            .render_to_writecolor(&mut buf, &first, &config)
+            .unwrap();
+        let mut full = Ansi::new(vec![]);
+        print_image(&mut full, &first, &config).unwrap();
+        assert_eq!(buf.get_ref(), full.get_ref());
 
-        c.set_fg(Some(Color::Rgb(10, 20, 30)));
-        c.set_bg(Some(Color::Rgb(15, 25, 35)));
+        // nothing changed, nothing is printed
+        let mut buf = Ansi::new(vec![]);
+        renderer
+            .render_to_writecolor(&mut buf, &first, &config)
+            .unwrap();
+        assert!(buf.get_ref().is_empty());
 
-        write_colored_character(&mut buf, &c, false).unwrap();
+        img.put_pixel(2, 3, Rgba([255, 0, 0, 255]));
+        let mut buf = Ansi::new(vec![]);
+        let (w, h) = renderer
+            .render_to_writecolor(&mut buf, &DynamicImage::ImageRgba8(img), &config)
+            .unwrap();
+        assert_eq!((w, h), (4, 2));
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[0m\x1b[38;2;10;20;30m\x1b[48;2;15;25;35m▄"
+            "\x1b[1;1H\x1b[1E\x1b[3G\x1b[0m\x1b[38;5;196m\x1b[48;5;241m▄\x1b[0m\x1b[1E"
         );
     }
 
     #[test]
-    fn test_write_colored_char_no_color() {
+    fn test_block_renderer_last_column() {
+        let mut img = image::RgbaImage::new(4, 2);
+        let mut renderer = BlockRenderer::new();
+        let config = Config {
+            truecolor: false,
+            x: u16::MAX - 1,
+            ..Default::default()
+        };
+
         let mut buf = Ansi::new(vec![]);
-        let c = ColorSpec::new();
+        renderer
+            .render_to_writecolor(&mut buf, &DynamicImage::ImageRgba8(img.clone()), &config)
+            .unwrap();
 
-        write_colored_character(&mut buf, &c, false).unwrap();
-        // expect to print nothing, just move cursor to the right
-        assert_eq!(std::str::from_utf8(buf.get_ref()).unwrap(), "\x1b[1C");
+        img.put_pixel(2, 0, Rgba([255, 0, 0, 255]));
+        let mut buf = Ansi::new(vec![]);
+        renderer
+            .render_to_writecolor(&mut buf, &DynamicImage::ImageRgba8(img), &config)
+            .unwrap();
+        assert!(std::str::from_utf8(buf.get_ref())
+            .unwrap()
+            .contains("\x1b[65535G"));
     }
 
     #[test]
-    fn test_write_colored_char_last_row_bg() {
+    fn test_block_renderer_relative_erases_cells() {
+        let mut img = image::RgbaImage::new(3, 2);
+        img.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+        img.put_pixel(1, 1, Rgba([0, 0, 255, 255]));
+        let mut renderer = BlockRenderer::new();
+
+        let config = Config {
+            transparent: true,
+            truecolor: false,
+            absolute_offset: false,
+            x: 2,
+            ..Default::default()
+        };
+
         let mut buf = Ansi::new(vec![]);
-        let mut c = ColorSpec::new();
+        renderer
+            .render_to_writecolor(&mut buf, &DynamicImage::ImageRgba8(img), &config)
+            .unwrap();
 
-        c.set_bg(Some(Color::Rgb(10, 20, 30)));
+        let mut buf = Ansi::new(vec![]);
+        let img = image::RgbaImage::new(3, 2);
+        renderer
+            .render_to_writecolor(&mut buf, &DynamicImage::ImageRgba8(img), &config)
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(buf.get_ref()).unwrap(),
+            "\x1b[1F\x1b[4G\x1b[0m \x1b[1E"
+        );
 
-        write_colored_character(&mut buf, &c, true).unwrap();
+        // a different size triggers a full redraw
+        let mut buf = Ansi::new(vec![]);
+        let mut img = image::RgbaImage::new(2, 2);
+        img.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+        renderer
+            .render_to_writecolor(&mut buf, &DynamicImage::ImageRgba8(img), &config)
+            .unwrap();
         assert_eq!(
             std::str::from_utf8(buf.get_ref()).unwrap(),
-            "\x1b[0m\x1b[38;2;10;20;30m▀"
+            "\x1b[3C\x1b[0m\x1b[38;5;21m▀\x1b[0m\n"
         );
     }
 
-    #[test]
-    fn test_write_colored_char_last_row_no_bg() {
+    fn write_cells(cells: &[Cell], is_last_row: bool) -> String {
         let mut buf = Ansi::new(vec![]);
-        let mut c = ColorSpec::new();
+        let mut writer = CellWriter::new(&mut buf);
+        for cell in cells {
+            writer.write_cell(cell, is_last_row).unwrap();
+        }
+        String::from_utf8(buf.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_write_cell_only_bottom() {
+        let cell = Cell {
+            top: None,
+            bottom: Some(Color::Rgb(10, 20, 30)),
+        };
+        assert_eq!(write_cells(&[cell], false), "\x1b[0m\x1b[38;2;10;20;30m▄");
+    }
+
+    #[test]
+    fn test_write_cell_only_top() {
+        let cell = Cell {
+            top: Some(Color::Rgb(50, 60, 70)),
+            bottom: None,
+        };
+        assert_eq!(write_cells(&[cell], false), "\x1b[0m\x1b[38;2;50;60;70m▀");
+    }
+
+    #[test]
+    fn test_write_cell_top_and_bottom() {
+        let cell = Cell {
+            top: Some(Color::Rgb(15, 25, 35)),
+            bottom: Some(Color::Rgb(10, 20, 30)),
+        };
+        assert_eq!(
+            write_cells(&[cell], false),
+            "\x1b[0m\x1b[38;2;10;20;30m\x1b[48;2;15;25;35m▄"
+        );
+    }
+
+    #[test]
+    fn test_write_cell_single_color() {
+        let cell = Cell {
+            top: Some(Color::Rgb(1, 2, 3)),
+            bottom: Some(Color::Rgb(1, 2, 3)),
+        };
+        // a space with a background is enough
+        assert_eq!(write_cells(&[cell], false), "\x1b[0m\x1b[48;2;1;2;3m ");
+    }
+
+    #[test]
+    fn test_write_cell_no_color() {
+        let cell = Cell {
+            top: None,
+            bottom: None,
+        };
+        // expect to print nothing, the cursor is only moved before the next colored cell
+        assert_eq!(write_cells(&[cell, cell], false), "");
 
-        // test with no color
-        write_colored_character(&mut buf, &c, true).unwrap();
-        assert_eq!(std::str::from_utf8(buf.get_ref()).unwrap(), "\x1b[1C");
+        let colored = Cell {
+            top: Some(Color::Rgb(1, 2, 3)),
+            bottom: None,
+        };
+        assert_eq!(
+            write_cells(&[cell, cell, colored], false),
+            "\x1b[2C\x1b[0m\x1b[38;2;1;2;3m▀"
+        );
+    }
+
+    #[test]
+    fn test_write_cell_last_row() {
+        let mut cell = Cell {
+            top: Some(Color::Rgb(10, 20, 30)),
+            bottom: Some(Color::Rgb(40, 50, 60)),
+        };
+        // the bottom half is not part of the image
+        assert_eq!(write_cells(&[cell], true), "\x1b[0m\x1b[38;2;10;20;30m▀");
+
+        cell.top = None;
+        assert_eq!(write_cells(&[cell], true), "");
+    }
+
+    #[test]
+    fn test_write_cell_skips_redundant_colors() {
+        let a = Color::Rgb(1, 1, 1);
+        let b = Color::Rgb(2, 2, 2);
+        let cells = [
+            Cell {
+                top: Some(a),
+                bottom: Some(b),
+            },
+            Cell {
+                top: Some(a),
+                bottom: Some(b),
+            },
+            // flipped colors are drawn with the upper block instead
+            Cell {
+                top: Some(b),
+                bottom: Some(a),
+            },
+            // the background has to be cleared
+            Cell {
+                top: Some(a),
+                bottom: None,
+            },
+        ];
+        assert_eq!(
+            write_cells(&cells, false),
+            "\x1b[0m\x1b[38;2;2;2;2m\x1b[48;2;1;1;1m▄▄▀\x1b[0m\x1b[38;2;1;1;1m▀"
+        );
+    }
 
-        c.set_fg(Some(Color::Rgb(10, 20, 30)));
+    // Size of the output when colors are set for every single cell
+    fn unoptimized_len(img: &DynamicImage, config: &Config) -> usize {
+        let grid = CellGrid::new(img, &Layout::new(img, config).unwrap(), config);
+        let mut buf = Ansi::new(vec![]);
+        for row in 0..grid.height {
+            for cell in grid.row(row) {
+                let mut spec = ColorSpec::new();
+                spec.set_bg(cell.top).set_fg(cell.bottom);
+                buf.set_color(&spec).unwrap();
+                write!(buf, "{}", LOWER_HALF_BLOCK).unwrap();
+            }
+            buf.reset().unwrap();
+            writeln!(buf, "\r").unwrap();
+        }
+        buf.get_ref().len()
+    }
 
-        // test with fg (unusual case)
+    fn optimized_len(img: &DynamicImage, config: &Config) -> usize {
         let mut buf = Ansi::new(vec![]);
-        write_colored_character(&mut buf, &c, true).unwrap();
-        assert_eq!(std::str::from_utf8(buf.get_ref()).unwrap(), "\x1b[1C");
+        print_image(&mut buf, img, config).unwrap();
+        buf.get_ref().len()
+    }
+
+    #[test]
+    fn test_output_size_reduction() {
+        let config = Config {
+            truecolor: true,
+            absolute_offset: false,
+            width: Some(Size::Cells(60)),
+            height: Some(Size::Cells(20)),
+            ..Default::default()
+        };
+
+        // solid color: one SGR sequence per row
+        let solid = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            60,
+            40,
+            Rgba([200, 30, 30, 255]),
+        ));
+        let (before, after) = (
+            unoptimized_len(&solid, &config),
+            optimized_len(&solid, &config),
+        );
+        assert!(after * 20 < before, "{} -> {}", before, after);
+
+        // horizontal gradient: identical halves are printed as spaces
+        let mut gradient = DynamicImage::ImageRgba8(image::RgbaImage::new(60, 40));
+        image::imageops::horizontal_gradient(
+            &mut gradient,
+            &Rgba([0, 0, 0, 255]),
+            &Rgba([255, 255, 255, 255]),
+        );
+        let (before, after) = (
+            unoptimized_len(&gradient, &config),
+            optimized_len(&gradient, &config),
+        );
+        assert!(after * 2 < before, "{} -> {}", before, after);
+
+        // transparent image: the checkerboard only needs to be set up once per row
+        let empty = DynamicImage::ImageRgba8(image::RgbaImage::new(60, 40));
+        let (before, after) = (
+            unoptimized_len(&empty, &config),
+            optimized_len(&empty, &config),
+        );
+        assert!(after * 4 < before, "{} -> {}", before, after);
     }
 }
@@ -0,0 +1,246 @@
+use crate::config::{Border, Caption, CaptionPosition, Config};
+use crate::error::ViuResult;
+use crate::printer::layout::Layout;
+use crate::printer::{adjust_offset, cursor_position};
+use console::{measure_text_width, truncate_str};
+use crossterm::cursor::{MoveRight, MoveTo};
+use crossterm::execute;
+use std::io::Write;
+
+/// Border, padding and caption drawn around an image, see [Config::border].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Decoration {
+    border: Option<Border>,
+    padding: u32,
+    caption: Option<Caption>,
+}
+
+impl Decoration {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            border: config.border,
+            padding: config.padding,
+            caption: config.caption.clone(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.border.is_none() && self.padding == 0 && self.caption.is_none()
+    }
+
+    /// Cells taken up on the left, top, right and bottom of the image.
+    pub(crate) fn insets(&self) -> (u32, u32, u32, u32) {
+        let side = self.border.is_some() as u32 + self.padding;
+        let (above, below) = match &self.caption {
+            Some(c) if c.position == CaptionPosition::Above => (1, 0),
+            Some(_) => (0, 1),
+            None => (0, 0),
+        };
+        (side, above + side, side, below + side)
+    }
+
+    /// Draw the decoration, clearing the space inside of it, and return the config to print
+    /// the image with. In relative mode, the cursor is moved back up to the image.
+    pub(crate) fn draw(
+        &self,
+        stdout: &mut impl Write,
+        layout: &Layout,
+        config: &Config,
+    ) -> ViuResult<Config> {
+        let placed = layout.place(config);
+        let x = config
+            .x
+            .saturating_add(u16::try_from(layout.box_x).unwrap_or(u16::MAX));
+        let y = config
+            .y
+            .saturating_add(i16::try_from(layout.box_y).unwrap_or(i16::MAX));
+        let rows = self.rows(layout.box_width, layout.box_height);
+
+        if config.absolute_offset {
+            for (i, row) in rows.iter().enumerate() {
+                // a negative y offset is reported by adjust_offset
+                let row_y = (std::cmp::max(0, y) as u16)
+                    .saturating_add(u16::try_from(i).unwrap_or(u16::MAX));
+                execute!(stdout, MoveTo(cursor_position(x), cursor_position(row_y)))?;
+                write!(stdout, "{row}")?;
+            }
+            return Ok(placed);
+        }
+
+        let start = Config {
+            x: 0,
+            y,
+            ..config.clone()
+        };
+        adjust_offset(stdout, &start)?;
+        for row in &rows {
+            if x > 0 {
+                execute!(stdout, MoveRight(x))?;
+            }
+            write!(stdout, "{row}\r\n")?;
+        }
+        Ok(Config {
+            y: -i16::try_from(layout.box_height - layout.offset_y).unwrap_or(i16::MAX),
+            ..placed
+        })
+    }
+
+    /// Lines making up a decorated box of the given size, in terminal cells.
+    fn rows(&self, width: u32, height: u32) -> Vec<String> {
+        let (_, top, _, bottom) = self.insets();
+        let caption_row = match &self.caption {
+            Some(c) if c.position == CaptionPosition::Above => Some(0),
+            Some(_) => Some(height - 1),
+            None => None,
+        };
+        // the border goes around everything but the caption
+        let (first, last) = match self.border {
+            Some(_) => (top - 1 - self.padding, height - bottom + self.padding),
+            None => (u32::MAX, u32::MAX),
+        };
+        let [top_left, top_right, bottom_left, bottom_right, horizontal, vertical] =
+            self.border.map(border_chars).unwrap_or([' '; 6]);
+        let inner = width.saturating_sub(2) as usize;
+
+        (0..height)
+            .map(|row| match row {
+                _ if Some(row) == caption_row => {
+                    let caption = self.caption.as_ref().unwrap();
+                    caption_line(caption, width as usize)
+                }
+                _ if row == first => format!(
+                    "{top_left}{}{top_right}",
+                    horizontal.to_string().repeat(inner)
+                ),
+                _ if row == last => format!(
+                    "{bottom_left}{}{bottom_right}",
+                    horizontal.to_string().repeat(inner)
+                ),
+                _ if self.border.is_some() => {
+                    format!("{vertical}{}{vertical}", " ".repeat(inner))
+                }
+                _ => " ".repeat(width as usize),
+            })
+            .collect()
+    }
+}
+
+/// Corners, then the horizontal and vertical lines.
+fn border_chars(border: Border) -> [char; 6] {
+    match border {
+        Border::Single => ['┌', '┐', '└', '┘', '─', '│'],
+        Border::Rounded => ['╭', '╮', '╰', '╯', '─', '│'],
+        Border::Heavy => ['┏', '┓', '┗', '┛', '━', '┃'],
+        Border::Double => ['╔', '╗', '╚', '╝', '═', '║'],
+    }
+}
+
+/// The caption aligned in a line of `width` cells, cut off with an ellipsis if needed. Wide
+/// characters, like CJK and emoji, take up two cells.
+fn caption_line(caption: &Caption, width: usize) -> String {
+    if width == 0 {
+        return String::new();
+    }
+    let text = truncate_str(&caption.text, width, "…");
+    // a wide character which does not fit leaves one cell empty
+    let len = std::cmp::min(measure_text_width(&text), width);
+    let left = caption.align.offset((width - len) as u32) as usize;
+    format!(
+        "{}{}{}",
+        " ".repeat(left),
+        text,
+        " ".repeat(width - len - left)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HorizontalAlign;
+
+    fn decoration(border: Option<Border>, padding: u32, caption: Option<Caption>) -> Decoration {
+        Decoration {
+            border,
+            padding,
+            caption,
+        }
+    }
+
+    #[test]
+    fn test_insets() {
+        assert!(decoration(None, 0, None).is_empty());
+        assert_eq!(
+            decoration(Some(Border::Single), 1, None).insets(),
+            (2, 2, 2, 2)
+        );
+        let caption = Caption {
+            position: CaptionPosition::Above,
+            ..Caption::new("a")
+        };
+        assert_eq!(
+            decoration(Some(Border::Single), 0, Some(caption)).insets(),
+            (1, 2, 1, 1)
+        );
+        assert_eq!(
+            decoration(None, 0, Some(Caption::new("a"))).insets(),
+            (0, 0, 0, 1)
+        );
+    }
+
+    #[test]
+    fn test_draw_saturates() {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+        let config = Config {
+            x: u16::MAX,
+            y: i16::MAX,
+            border: Some(Border::Single),
+            ..Default::default()
+        };
+        let layout = Layout::new(&img, &config).unwrap();
+        let mut vec = Vec::new();
+        let placed = Decoration::new(&config)
+            .draw(&mut vec, &layout, &config)
+            .unwrap();
+        assert_eq!((placed.x, placed.y), (u16::MAX, i16::MAX));
+
+        let result = String::from_utf8(vec).unwrap();
+        assert!(result.starts_with("\x1b[32768;65535H┌"));
+        assert!(result.contains("\x1b[32770;65535H└"));
+    }
+
+    #[test]
+    fn test_rows() {
+        let deco = decoration(Some(Border::Rounded), 0, Some(Caption::new("img")));
+        assert_eq!(deco.rows(5, 4), vec!["╭───╮", "│   │", "╰───╯", " img "]);
+
+        let caption = Caption {
+            position: CaptionPosition::Above,
+            align: HorizontalAlign::Left,
+            ..Caption::new("image.png")
+        };
+        let deco = decoration(Some(Border::Double), 1, Some(caption));
+        assert_eq!(
+            deco.rows(6, 6),
+            vec!["image…", "╔════╗", "║    ║", "║    ║", "║    ║", "╚════╝"]
+        );
+
+        let deco = decoration(None, 1, None);
+        assert_eq!(deco.rows(3, 3), vec!["   "; 3]);
+    }
+
+    #[test]
+    fn test_caption_line_wide() {
+        let caption = Caption::new("画像");
+        assert_eq!(caption_line(&caption, 6), " 画像 ");
+        assert_eq!(caption_line(&caption, 4), "画像");
+
+        let caption = Caption {
+            align: HorizontalAlign::Left,
+            ..Caption::new("画像ファイル🖼")
+        };
+        assert_eq!(caption_line(&caption, 7), "画像フ…");
+        assert_eq!(caption_line(&caption, 8), "画像フ… ");
+        assert_eq!(caption_line(&caption, 1), "…");
+        assert_eq!(caption_line(&caption, 0), "");
+    }
+}
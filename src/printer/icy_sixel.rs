@@ -1,47 +1,69 @@
-use super::{adjust_offset, find_best_fit, Printer, ReadKey};
+use super::backdrop::{flatten, half_cell};
+use super::parallel::rgba8;
+use super::{adjust_offset, Encoded, Printer, ReadKey, Source};
 use icy_sixel::sixel_string;
-use image::{imageops::FilterType, GenericImageView};
+use image::imageops::FilterType;
 
 #[derive(Debug)]
 pub struct IcySixelPrinter;
 
 impl Printer for IcySixelPrinter {
-    fn print(
+    fn print_source(
         &self,
         _stdin: &impl ReadKey,
         stdout: &mut impl std::io::Write,
-        img: &image::DynamicImage,
+        source: &Source,
         config: &crate::Config,
     ) -> crate::ViuResult<(u32, u32)> {
-        let (w, h) = find_best_fit(img, config.width, config.height);
-
-        //TODO: the max 1000 width is an xterm bug workaround, other terminals may not be affected
-        let resized_img =
-            img.resize_exact(std::cmp::min(6 * w, 1000), 12 * h, FilterType::Triangle);
-
-        let (width, height) = resized_img.dimensions();
-
-        let rgba = resized_img.to_rgba8();
-        let raw = rgba.as_raw();
-
-        adjust_offset(stdout, config)?;
-
-        match sixel_string(
-            raw,
-            width as i32,
-            height as i32,
-            icy_sixel::PixelFormat::RGBA8888,
-            icy_sixel::DiffusionMethod::Auto,
-            icy_sixel::MethodForLargest::Auto,
-            icy_sixel::MethodForRep::Auto,
-            icy_sixel::Quality::AUTO,
-        ) {
-            Ok(output) => {
-                write!(stdout, "{output}")?;
-                stdout.flush()?;
-                Ok((w, h))
-            }
-            Err(error) => Err(crate::ViuError::IcySixelError(format!("{error}"))),
-        }
+        write_encoded(stdout, &encode(source, config)?, config)
     }
 }
+
+/// Encode the image for [write_encoded].
+pub(crate) fn encode(source: &Source, config: &crate::Config) -> crate::ViuResult<Encoded> {
+    let layout = source.layout;
+    let (w, h) = (layout.width, layout.height);
+
+    //TODO: the max 1000 width is an xterm bug workaround, other terminals may not be affected
+    let (width, height) = (std::cmp::min(6 * w, 1000), 12 * h);
+    let resized_img = layout.resize(&source.img, width, height, config, FilterType::Triangle);
+
+    // sixel has no notion of partial transparency, so blend with the backdrop here
+    let rgba = flatten(&rgba8(&resized_img), config, half_cell());
+    let raw = rgba.as_raw();
+
+    match sixel_string(
+        raw,
+        width as i32,
+        height as i32,
+        icy_sixel::PixelFormat::RGBA8888,
+        icy_sixel::DiffusionMethod::Auto,
+        icy_sixel::MethodForLargest::Auto,
+        icy_sixel::MethodForRep::Auto,
+        icy_sixel::Quality::AUTO,
+    ) {
+        Ok(output) => Ok(Encoded {
+            layout,
+            data: vec![output.into_bytes()],
+        }),
+        Err(error) => Err(crate::ViuError::IcySixelError(format!("{error}"))),
+    }
+}
+
+/// Print an image encoded by [encode].
+pub(crate) fn write_encoded(
+    stdout: &mut impl std::io::Write,
+    encoded: &Encoded,
+    config: &crate::Config,
+) -> crate::ViuResult<(u32, u32)> {
+    let layout = &encoded.layout;
+    let placed = layout.start(stdout, config)?;
+    adjust_offset(stdout, &placed)?;
+
+    for data in &encoded.data {
+        stdout.write_all(data)?;
+    }
+    layout.finish(stdout)?;
+    stdout.flush()?;
+    Ok((layout.box_width, layout.box_height))
+}
@@ -1,5 +1,6 @@
 use crate::error::ViuResult;
-use crate::printer::{adjust_offset, find_best_fit, Printer, ReadKey};
+use crate::printer::layout::Layout;
+use crate::printer::{adjust_offset, Encoded, Printer, ReadKey, Source};
 use crate::Config;
 use base64::{engine::general_purpose, Engine};
 use image::{DynamicImage, GenericImageView, ImageEncoder};
@@ -7,10 +8,9 @@ use std::io::Write;
 use std::sync::LazyLock;
 
 #[cfg(feature = "print-file")]
-use std::{
-    io::{BufReader, Read},
-    path::Path,
-};
+use crate::printer::orientation::{decode, passthrough};
+#[cfg(feature = "print-file")]
+use std::{io::Cursor, path::Path};
 
 #[allow(non_camel_case_types)]
 #[derive(Debug)]
@@ -24,70 +24,105 @@ pub fn is_iterm_supported() -> bool {
 }
 
 impl Printer for iTermPrinter {
-    fn print(
+    fn print_source(
         &self,
         _stdin: &impl ReadKey,
         stdout: &mut impl Write,
-        img: &DynamicImage,
+        source: &Source,
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
-        let (width, height) = img.dimensions();
-
-        // Transform the dynamic image to a PNG which can be given directly to iTerm
-        let mut png_bytes: Vec<u8> = Vec::new();
-        image::codecs::png::PngEncoder::new(&mut png_bytes).write_image(
-            img.as_bytes(),
-            width,
-            height,
-            img.color().into(),
-        )?;
-
-        print_buffer(stdout, img, &png_bytes[..], config)
+        write_encoded(stdout, &encode(source)?, config)
     }
 
     #[cfg(feature = "print-file")]
     fn print_from_file<P: AsRef<Path>>(
         &self,
-        _stdin: &impl ReadKey,
+        stdin: &impl ReadKey,
         stdout: &mut impl Write,
         filename: P,
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
-        let file = std::fs::File::open(filename)?;
-
-        // load the file content
-        let mut buf_reader = BufReader::new(file);
-        let mut file_content = Vec::new();
-        buf_reader.read_to_end(&mut file_content)?;
+        let file_content = std::fs::read(filename)?;
+        self.print_from_bytes(stdin, stdout, &file_content, config)
+    }
 
-        let img = image::load_from_memory(&file_content[..])?;
-        print_buffer(stdout, &img, &file_content[..], config)
+    #[cfg(feature = "print-file")]
+    fn print_from_bytes(
+        &self,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        bytes: &[u8],
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        // iTerm cannot rotate or crop by itself, otherwise the original file is passed through
+        let Some((_, layout)) = passthrough(bytes, config)? else {
+            let (img, _) = decode(image::ImageReader::new(Cursor::new(bytes)), config)?;
+            return self.print(stdin, stdout, &img, config);
+        };
+        let encoded = Encoded {
+            data: vec![sequence(&layout, bytes)],
+            layout,
+        };
+        write_encoded(stdout, &encoded, config)
     }
 }
 
-/// This function requires both a DynamicImage, which is used to calculate dimensions,
-/// and it's raw representation as a file, because that's the data iTerm needs to display it.
-fn print_buffer(
-    stdout: &mut impl Write,
-    img: &DynamicImage,
-    img_content: &[u8],
-    config: &Config,
-) -> ViuResult<(u32, u32)> {
-    adjust_offset(stdout, config)?;
+/// Transform the dynamic image to a PNG which can be given directly to iTerm
+fn encode_png(img: &DynamicImage) -> ViuResult<Vec<u8>> {
+    let (width, height) = img.dimensions();
 
-    let (w, h) = find_best_fit(img, config.width, config.height);
+    let mut png_bytes: Vec<u8> = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png_bytes).write_image(
+        img.as_bytes(),
+        width,
+        height,
+        img.color().into(),
+    )?;
+    Ok(png_bytes)
+}
 
-    writeln!(
-        stdout,
-        "\x1b]1337;File=inline=1;preserveAspectRatio=1;size={};width={};height={}:{}\x07",
+/// The escape sequence displaying the image, given the raw content of its file, which is
+/// what iTerm needs. The layout holds the dimensions.
+fn sequence(layout: &Layout, img_content: &[u8]) -> Vec<u8> {
+    format!(
+        "\x1b]1337;File=inline=1;preserveAspectRatio={};size={};width={};height={}:{}\x07",
+        if layout.stretch { 0 } else { 1 },
         img_content.len(),
-        w,
-        h,
+        layout.width,
+        layout.height,
         general_purpose::STANDARD.encode(img_content)
-    )?;
+    )
+    .into_bytes()
+}
+
+/// Encode the image for [write_encoded].
+pub(crate) fn encode(source: &Source) -> ViuResult<Encoded> {
+    let layout = source.layout;
+    let png_bytes = encode_png(&layout.crop(&source.img))?;
+    Ok(Encoded {
+        layout,
+        data: vec![sequence(&layout, &png_bytes)],
+    })
+}
+
+/// Print an image encoded by [encode].
+pub(crate) fn write_encoded(
+    stdout: &mut impl Write,
+    encoded: &Encoded,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let layout = &encoded.layout;
+    let placed = layout.start(stdout, config)?;
+    adjust_offset(stdout, &placed)?;
+
+    for data in &encoded.data {
+        stdout.write_all(data)?;
+    }
+    writeln!(stdout)?;
+    layout.finish(stdout)?;
     stdout.flush()?;
 
-    Ok((w, h))
+    Ok((layout.box_width, layout.box_height))
 }
 
 /// Check if the iTerm protocol can be used
@@ -128,6 +163,7 @@ mod tests {
     use crate::printer::TestKeys;
 
     use super::*;
+    use crate::Size;
     use image::GenericImage;
 
     #[test]
@@ -150,4 +186,113 @@ mod tests {
         );
         assert_eq!(std::str::from_utf8(&vec).unwrap(), "\x1b[4;5H\x1b]1337;File=inline=1;preserveAspectRatio=1;size=95;width=2;height=2:iVBORw0KGgoAAAANSUhEUgAAAAIAAAADCAYAAAC56t6BAAAAJklEQVR4AQEbAOT/AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAACBAYIAEMAFdTlTsEAAAAASUVORK5CYII=\x07\n");
     }
+
+    #[test]
+    fn test_print_fill() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 3));
+
+        let config = Config {
+            width: Some(Size::Cells(6)),
+            height: Some(Size::Cells(2)),
+            fit: crate::Fit::Fill,
+            ..Default::default()
+        };
+        let mut vec = Vec::new();
+
+        assert_eq!(
+            iTermPrinter
+                .print(&TestKeys::new(&[]), &mut vec, &img, &config)
+                .unwrap(),
+            (6, 2)
+        );
+        let result = std::str::from_utf8(&vec).unwrap();
+        assert!(result.starts_with("\x1b[1;1H\x1b]1337;File=inline=1;preserveAspectRatio=0;"));
+        assert!(result.contains(";width=6;height=2:"));
+    }
+
+    #[test]
+    fn test_print_decorated() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+
+        let config = Config {
+            x: 1,
+            y: 1,
+            border: Some(crate::Border::Single),
+            caption: Some(crate::Caption::new("ab")),
+            ..Default::default()
+        };
+        let mut vec = Vec::new();
+
+        // the decoration is part of the returned size
+        assert_eq!(
+            iTermPrinter
+                .print(&TestKeys::new(&[]), &mut vec, &img, &config)
+                .unwrap(),
+            (4, 4)
+        );
+        let result = std::str::from_utf8(&vec).unwrap();
+        assert!(result.starts_with(
+            "\x1b[2;2H┌──┐\x1b[3;2H│  │\x1b[4;2H└──┘\x1b[5;2H ab \x1b[3;3H\x1b]1337;File=inline=1;"
+        ));
+        assert!(result.contains(";width=2;height=1:"));
+        assert!(result.ends_with("\x07\n\n\n"));
+    }
+
+    #[test]
+    fn test_print_decorated_relative() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+
+        let config = Config {
+            x: 1,
+            absolute_offset: false,
+            border: Some(crate::Border::Single),
+            ..Default::default()
+        };
+        let mut vec = Vec::new();
+
+        iTermPrinter
+            .print(&TestKeys::new(&[]), &mut vec, &img, &config)
+            .unwrap();
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        // the frame is drawn first, then the cursor goes back up to the image
+        assert!(result
+            .starts_with("\x1b[1C┌──┐\r\n\x1b[1C│  │\r\n\x1b[1C└──┘\r\n\x1b[2F\x1b[2C\x1b]1337;"));
+        assert!(result.ends_with("\x07\n\n"));
+    }
+
+    #[cfg(feature = "print-file")]
+    #[test]
+    fn test_print_from_bytes() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 3));
+        let png = encode_png(&img).unwrap();
+        let stdin = TestKeys::new(&[]);
+
+        // the original bytes are passed through
+        let mut vec = Vec::new();
+        let config = Config::default();
+        assert_eq!(
+            iTermPrinter
+                .print_from_bytes(&stdin, &mut vec, &png, &config)
+                .unwrap(),
+            (2, 2)
+        );
+        let expected = sequence(&Layout::new(&img, &config).unwrap(), &png);
+        assert!(vec.windows(expected.len()).any(|w| w == expected));
+
+        // rotated images are decoded
+        let mut vec = Vec::new();
+        let config = Config {
+            rotate: crate::Rotation::Deg90,
+            ..Default::default()
+        };
+        assert_eq!(
+            iTermPrinter
+                .print_from_bytes(&stdin, &mut vec, &png, &config)
+                .unwrap(),
+            (3, 1)
+        );
+        let expected = general_purpose::STANDARD.encode(&png);
+        assert!(!std::str::from_utf8(&vec).unwrap().contains(&expected));
+    }
 }
@@ -1,19 +1,37 @@
 use crate::error::{ViuError, ViuResult};
-use crate::printer::{adjust_offset, find_best_fit, Printer, ReadKey};
-use crate::utils::terminal_size;
+use crate::printer::layout::Layout;
+use crate::printer::parallel::{for_each_row, rgba8};
+use crate::printer::{adjust_offset, Encoded, Printer, ReadKey, Source};
 use crate::Config;
 use base64::{engine::general_purpose, Engine};
-use console::{Key, Term};
+use console::Key;
+use crossterm::execute;
+use crossterm::terminal::{Clear, ClearType};
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::Write;
 use std::io::{Error, ErrorKind};
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex};
 use tempfile::NamedTempFile;
 
+#[cfg(feature = "print-file")]
+use crate::printer::orientation::{decode, passthrough};
+#[cfg(feature = "print-file")]
+use image::ImageFormat;
+#[cfg(feature = "print-file")]
+use std::{io::Cursor, path::Path};
+
 #[derive(Debug)]
 pub struct KittyPrinter;
 
 const TEMP_FILE_PREFIX: &str = ".tty-graphics-protocol.viuer.";
+// Image id to replace images with, if none is configured
+pub(crate) const DEFAULT_KITTY_IMAGE_ID: u32 = 0x7669_7565;
 static KITTY_SUPPORT: LazyLock<KittySupport> = LazyLock::new(check_kitty_support);
+// Fingerprints of the images stored by the terminal, by their configured id
+static STORED_IMAGES: LazyLock<Mutex<HashMap<u32, u64>>> = LazyLock::new(Default::default);
 
 /// Returns the terminal's support for the Kitty graphics protocol.
 pub fn get_kitty_support() -> KittySupport {
@@ -21,33 +39,91 @@ pub fn get_kitty_support() -> KittySupport {
 }
 
 impl Printer for KittyPrinter {
-    fn print(
+    fn print_source(
+        &self,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        source: &Source,
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        let layout = source.layout;
+        print_payload(
+            stdin,
+            stdout,
+            &Payload::new(&source.img, &layout),
+            &layout,
+            config,
+        )
+    }
+
+    #[cfg(feature = "print-file")]
+    fn print_from_file<P: AsRef<Path>>(
+        &self,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        filename: P,
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        let file_content = std::fs::read(filename)?;
+        self.print_from_bytes(stdin, stdout, &file_content, config)
+    }
+
+    #[cfg(feature = "print-file")]
+    fn print_from_bytes(
         &self,
         stdin: &impl ReadKey,
         stdout: &mut impl Write,
-        img: &image::DynamicImage,
+        bytes: &[u8],
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
-        let result = match get_kitty_support() {
-            KittySupport::None => Err(ViuError::KittyNotSupported),
-            KittySupport::Local => {
-                // print from file
-                print_local(stdin, stdout, img, config)
+        // Kitty reads PNG files by itself, other formats are decoded
+        match passthrough(bytes, config)? {
+            Some((ImageFormat::Png, layout)) => {
+                let payload = Payload::png(bytes, &layout);
+                print_payload(stdin, stdout, &payload, &layout, config)
             }
-            KittySupport::Remote => {
-                // print through escape codes
-                print_remote(stdin, stdout, img, config)
+            _ => {
+                let (img, _) = decode(image::ImageReader::new(Cursor::new(bytes)), config)?;
+                self.print(stdin, stdout, &img, config)
             }
-        }?;
-
-        print_newline(stdout, config, result.0)?;
+        }
+    }
 
-        Ok(result)
+    fn clear(&self, stdout: &mut impl Write) -> ViuResult {
+        // images are not part of the text, so clearing the screen leaves them in place
+        write!(stdout, "\x1b_Ga=d\x1b\\")?;
+        stored_images().clear();
+        execute!(stdout, Clear(ClearType::All))?;
+        Ok(())
     }
+}
+
+/// Print the payload at the position in the config, from a file or through escape codes.
+fn print_payload(
+    stdin: &impl ReadKey,
+    stdout: &mut impl Write,
+    payload: &Payload,
+    layout: &Layout,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let config = &layout.start(stdout, config)?;
+
+    let (width, _) = match get_kitty_support() {
+        KittySupport::None => Err(ViuError::KittyNotSupported),
+        KittySupport::Local => {
+            // print from file
+            print_local(stdin, stdout, payload, layout, config)
+        }
+        KittySupport::Remote => {
+            // print through escape codes
+            print_remote(stdin, stdout, payload, layout, config)
+        }
+    }?;
+
+    print_newline(stdout, config, width)?;
+    layout.finish(stdout)?;
 
-    // TODO: guess_format() here in order to treat PNGs specially (f=100).
-    // Also, maybe get channel count and use f=24 or f=32 accordingly.
-    // fn print_from_file(&self, filename: &str, config: &Config) -> ViuResult<(u32, u32)> {}
+    Ok((layout.box_width, layout.box_height))
 }
 
 /// The cursor is pushed to the next line by Kitty if the image reaches the terminal's boundary.
@@ -57,7 +133,7 @@ impl Printer for KittyPrinter {
 /// Could be done with a cursor check through `crossterm::cursor::position`,
 /// but that alone doesn't justify enabling the `events` feature.
 fn print_newline(stdout: &mut impl Write, config: &Config, width: u32) -> ViuResult {
-    let (term_w, _) = terminal_size();
+    let (term_w, _) = config.terminal_size();
     if config.x + (width as u16) < term_w {
         writeln!(stdout)?;
     }
@@ -77,9 +153,10 @@ pub enum KittySupport {
 }
 
 /// Check if Kitty protocol can be used
+#[cfg(not(test))]
 fn check_kitty_support() -> KittySupport {
     let mut stdout = std::io::stdout();
-    let term = Term::stdout();
+    let term = console::Term::stdout();
 
     // first check if kitty protocol base-line(inline images / remote) is available
     if has_remote_support(&term, &mut stdout).is_ok() {
@@ -94,6 +171,13 @@ fn check_kitty_support() -> KittySupport {
     KittySupport::None
 }
 
+/// Returns a constant and only used when running the tests, as they have no terminal to
+/// query. Remote mode writes everything to the output, which the tests can check.
+#[cfg(test)]
+fn check_kitty_support() -> KittySupport {
+    KittySupport::Remote
+}
+
 /// Close the temporary file that was created, filtering out [`NotFound`](ErrorKind::NotFound) errors.
 fn close_tmp_file(temp_file: NamedTempFile) -> ViuResult {
     // Explicitly clean up when finished with the file because destructor, OS and Kitty are not deterministic.
@@ -267,25 +351,31 @@ fn has_local_support(stdin: &impl ReadKey, stdout: &mut impl Write) -> ViuResult
 fn print_local(
     stdin: &impl ReadKey,
     stdout: &mut impl Write,
-    img: &image::DynamicImage,
+    payload: &Payload,
+    layout: &Layout,
     config: &Config,
 ) -> ViuResult<(u32, u32)> {
-    let rgba = img.to_rgba8();
-    let raw_img = rgba.as_raw();
-    let temp_file = store_in_tmp_file(raw_img)?;
-
     adjust_offset(stdout, config)?;
 
     // get the desired width and height
-    let (w, h) = find_best_fit(img, config.width, config.height);
+    let (w, h) = (layout.width, layout.height);
+
+    if place_stored(stdout, payload, layout, config)? {
+        stdout.flush()?;
+        return Ok((w, h));
+    }
+    let temp_file = store_in_tmp_file(&payload.data)?;
 
     write!(
         stdout,
-        "\x1b_Gf=32,s={},v={},c={},r={},a=T,t=t;{}\x1b\\",
-        img.width(),
-        img.height(),
+        "\x1b_Gf={},s={},v={},c={},r={}{}{},a=T,t=t;{}\x1b\\",
+        payload.format,
+        payload.width,
+        payload.height,
         w,
         h,
+        payload.source_rect(),
+        image_id(config),
         general_purpose::STANDARD.encode(
             temp_file
                 .path()
@@ -299,48 +389,235 @@ fn print_local(
     wait_for_dsr(stdin, stdout)?;
 
     close_tmp_file(temp_file)?;
+    remember_stored(payload, config);
 
     Ok((w, h))
 }
 
 /// Print with escape codes
-// TODO: try compression
 fn print_remote(
     _stdin: &impl ReadKey,
     stdout: &mut impl Write,
-    img: &image::DynamicImage,
+    payload: &Payload,
+    layout: &Layout,
     config: &Config,
 ) -> ViuResult<(u32, u32)> {
-    let rgba = img.to_rgba8();
-    let raw = rgba.as_raw();
-    let encoded = general_purpose::STANDARD.encode(raw);
-    let mut iter = encoded.chars().peekable();
-
     adjust_offset(stdout, config)?;
+    if !place_stored(stdout, payload, layout, config)? {
+        write_remote(stdout, payload, layout, config)?;
+        remember_stored(payload, config);
+    }
+    stdout.flush()?;
+    Ok((layout.width, layout.height))
+}
 
-    let (w, h) = find_best_fit(img, config.width, config.height);
+fn stored_images() -> std::sync::MutexGuard<'static, HashMap<u32, u64>> {
+    STORED_IMAGES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
 
-    let first_chunk: String = iter.by_ref().take(4096).collect();
+/// Display the image stored by the terminal under the configured id again, if it has the same
+/// pixels, instead of sending them another time. Only the part that is displayed and its size
+/// change. Returns whether the image was stored.
+fn place_stored(
+    stdout: &mut impl Write,
+    payload: &Payload,
+    layout: &Layout,
+    config: &Config,
+) -> ViuResult<bool> {
+    let Some(id) = config.kitty_image_id else {
+        return Ok(false);
+    };
+    if stored_images().get(&id) != Some(&payload.fingerprint()) {
+        return Ok(false);
+    }
 
-    // write the first chunk, which describes the image
+    // remove the previous placements, but keep the image data
     write!(
         stdout,
-        "\x1b_Gf=32,a=T,t=d,s={},v={},c={},r={},m=1;{}\x1b\\",
-        img.width(),
-        img.height(),
-        w,
-        h,
-        first_chunk
+        "\x1b_Ga=d,d=i{}\x1b\\\x1b_Ga=p,c={},r={}{}{}\x1b\\",
+        image_id(config),
+        layout.width,
+        layout.height,
+        payload.source_rect(),
+        image_id(config),
     )?;
+    Ok(true)
+}
+
+/// Remember the image sent to the terminal under the configured id, see [place_stored].
+fn remember_stored(payload: &Payload, config: &Config) {
+    if let Some(id) = config.kitty_image_id {
+        stored_images().insert(id, payload.fingerprint());
+    }
+}
+
+// Pixel bytes in one chunk, which are exactly 4096 characters in base64, without padding
+const CHUNK_BYTES: usize = 3072;
+const CHUNK_CHARS: usize = 4096;
+
+// Chunks encoded at once, on all cores with the `rayon` feature
+#[cfg(feature = "rayon")]
+const CHUNK_BATCH: usize = 64;
+#[cfg(not(feature = "rayon"))]
+const CHUNK_BATCH: usize = 1;
+
+/// Write the escape codes transmitting and displaying the image, in chunks of 4096 bytes.
+/// The chunks are encoded into a buffer of a constant size, and written one by one.
+// TODO: try compression
+fn write_remote(
+    stdout: &mut impl Write,
+    payload: &Payload,
+    layout: &Layout,
+    config: &Config,
+) -> ViuResult {
+    let raw = &payload.data[..];
+    let total = raw.len().div_ceil(CHUNK_BYTES);
+
+    // write the start of the first chunk, which describes the image
+    write!(
+        stdout,
+        "\x1b_Gf={},a=T,t=d,s={},v={},c={},r={}{}{},m=1;",
+        payload.format,
+        payload.width,
+        payload.height,
+        layout.width,
+        layout.height,
+        payload.source_rect(),
+        image_id(config),
+    )?;
+
+    let mut buffer = vec![0; std::cmp::min(total, CHUNK_BATCH) * CHUNK_CHARS];
+    let mut index = 0;
+    for batch in raw.chunks(CHUNK_BYTES * CHUNK_BATCH) {
+        let encoded = &mut buffer[..batch.len().div_ceil(CHUNK_BYTES) * CHUNK_CHARS];
+        for_each_row(encoded, CHUNK_CHARS, |i, out| {
+            let chunk = &batch[i * CHUNK_BYTES..std::cmp::min((i + 1) * CHUNK_BYTES, batch.len())];
+            general_purpose::STANDARD
+                .encode_slice(chunk, out)
+                .expect("a chunk fits in the buffer");
+        });
+
+        for (chunk, out) in batch.chunks(CHUNK_BYTES).zip(encoded.chunks(CHUNK_CHARS)) {
+            // every chunk after the first one is a separate escape code
+            if index > 0 {
+                let m = if index + 1 < total { 1 } else { 0 };
+                write!(stdout, "\x1b\\\x1b_Gm={};", m)?;
+            }
+            stdout.write_all(&out[..chunk.len().div_ceil(3) * 4])?;
+            index += 1;
+        }
+    }
+    write!(stdout, "\x1b\\")?;
+    Ok(())
+}
+
+/// Encode the image for [write_encoded]. The escape codes are used regardless of the
+/// supported mode, as the temporary file of [print_local] can only be read once.
+pub(crate) fn encode(source: &Source, config: &Config) -> ViuResult<Encoded> {
+    let layout = source.layout;
+    let mut data = Vec::new();
+    write_remote(
+        &mut data,
+        &Payload::new(&source.img, &layout),
+        &layout,
+        config,
+    )?;
+    Ok(Encoded {
+        layout,
+        data: vec![data],
+    })
+}
 
-    // write all the chunks, each containing 4096 bytes of data
-    while iter.peek().is_some() {
-        let chunk: String = iter.by_ref().take(4096).collect();
-        let m = if iter.peek().is_some() { 1 } else { 0 };
-        write!(stdout, "\x1b_Gm={};{}\x1b\\", m, chunk)?;
+/// Print an image encoded by [encode].
+pub(crate) fn write_encoded(
+    stdout: &mut impl Write,
+    encoded: &Encoded,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let layout = &encoded.layout;
+    let config = &layout.start(stdout, config)?;
+    adjust_offset(stdout, config)?;
+    // the encoded image replaces the image stored under its id
+    if let Some(id) = config.kitty_image_id {
+        stored_images().remove(&id);
+    }
+    for data in &encoded.data {
+        stdout.write_all(data)?;
     }
     stdout.flush()?;
-    Ok((w, h))
+
+    print_newline(stdout, config, layout.width)?;
+    layout.finish(stdout)?;
+    Ok((layout.box_width, layout.box_height))
+}
+
+/// The whole image sent to the terminal, as 8 bit RGB or RGBA or as a PNG file, along with
+/// the part of it to display. Images which already are RGB or RGBA are borrowed.
+struct Payload<'a> {
+    // value of the `f` key
+    format: u32,
+    width: u32,
+    height: u32,
+    data: Cow<'a, [u8]>,
+    // part of the image to display, if it is cropped
+    crop: Option<(u32, u32, u32, u32)>,
+}
+
+impl<'a> Payload<'a> {
+    fn new(img: &'a DynamicImage, layout: &Layout) -> Self {
+        let (format, data) = match img {
+            DynamicImage::ImageRgb8(rgb) => (24, Cow::Borrowed(&rgb.as_raw()[..])),
+            DynamicImage::ImageRgba8(rgba) => (32, Cow::Borrowed(&rgba.as_raw()[..])),
+            _ => (32, rgba8(img).into_owned().into_raw().into()),
+        };
+        Self {
+            format,
+            width: img.width(),
+            height: img.height(),
+            data,
+            crop: layout.is_cropped(img).then_some(layout.crop),
+        }
+    }
+
+    /// A PNG file which is shown as a whole, decoded by the terminal.
+    #[cfg(feature = "print-file")]
+    fn png(bytes: &'a [u8], layout: &Layout) -> Self {
+        let (_, _, width, height) = layout.crop;
+        Self {
+            format: 100,
+            width,
+            height,
+            data: Cow::Borrowed(bytes),
+            crop: None,
+        }
+    }
+
+    /// Identifies the pixels, regardless of the part that is displayed.
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.format, self.width, self.height, &self.data[..]).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Keys selecting the part of the image to display, if it is cropped. The terminal does
+    /// the cropping, so the pixels are sent as they are.
+    fn source_rect(&self) -> String {
+        match self.crop {
+            Some((x, y, w, h)) => format!(",x={},y={},w={},h={}", x, y, w, h),
+            None => String::new(),
+        }
+    }
+}
+
+/// Keys identifying the image, if an id is configured. The terminal's responses are
+/// suppressed, as nothing reads them.
+fn image_id(config: &Config) -> String {
+    match config.kitty_image_id {
+        Some(id) => format!(",i={},q=2", id),
+        None => String::new(),
+    }
 }
 
 /// Create a file in temporary dir and write the byte slice to it.
@@ -361,6 +638,7 @@ mod tests {
     use crate::printer::TestKeys;
 
     use super::*;
+    use crate::Size;
     use image::{DynamicImage, GenericImage};
 
     #[test]
@@ -377,8 +655,16 @@ mod tests {
         let test_data = [Key::UnknownEscSeq(vec!['[', '0', 'n'])];
         let test_response = TestKeys::new(&test_data);
 
+        let layout = Layout::new(&img, &config).unwrap();
         assert_eq!(
-            print_local(&test_response, &mut vec, &img, &config).unwrap(),
+            print_local(
+                &test_response,
+                &mut vec,
+                &Payload::new(&img, &layout),
+                &layout,
+                &config
+            )
+            .unwrap(),
             (40, 13)
         );
         let result = std::str::from_utf8(&vec).unwrap();
@@ -404,8 +690,16 @@ mod tests {
         let test_data = [];
         let test_response = TestKeys::new(&test_data);
 
+        let layout = Layout::new(&img, &config).unwrap();
         assert_eq!(
-            print_remote(&test_response, &mut vec, &img, &config).unwrap(),
+            print_remote(
+                &test_response,
+                &mut vec,
+                &Payload::new(&img, &layout),
+                &layout,
+                &config
+            )
+            .unwrap(),
             (1, 1)
         );
         let result = std::str::from_utf8(&vec).unwrap();
@@ -417,6 +711,214 @@ mod tests {
         assert!(test_response.reached_end());
     }
 
+    #[test]
+    fn test_print_remote_cover() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 2));
+
+        let config = Config {
+            width: Some(Size::Cells(1)),
+            height: Some(Size::Cells(1)),
+            fit: crate::Fit::Cover,
+            ..Default::default()
+        };
+
+        let mut vec = Vec::new();
+        let layout = Layout::new(&img, &config).unwrap();
+        assert_eq!(
+            print_remote(
+                &TestKeys::new(&[]),
+                &mut vec,
+                &Payload::new(&img, &layout),
+                &layout,
+                &config
+            )
+            .unwrap(),
+            (1, 1)
+        );
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        // the whole image is sent, but only the center is displayed
+        assert!(
+            result.starts_with("\x1b[1;1H\x1b_Gf=32,a=T,t=d,s=4,v=2,c=1,r=1,x=1,y=0,w=1,h=2,m=1;")
+        );
+    }
+
+    #[test]
+    fn test_print_remote_source_rect() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4));
+
+        let config = Config {
+            source_rect: Some(crate::Rect::new(1, 2, 2, 2)),
+            ..Default::default()
+        };
+
+        let mut vec = Vec::new();
+        let layout = Layout::new(&img, &config).unwrap();
+        assert_eq!(
+            print_remote(
+                &TestKeys::new(&[]),
+                &mut vec,
+                &Payload::new(&img, &layout),
+                &layout,
+                &config
+            )
+            .unwrap(),
+            (2, 1)
+        );
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        // the terminal does the cropping
+        assert!(
+            result.starts_with("\x1b[1;1H\x1b_Gf=32,a=T,t=d,s=4,v=4,c=2,r=1,x=1,y=2,w=2,h=2,m=1;")
+        );
+    }
+
+    #[test]
+    fn test_print_remote_image_id() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+
+        let config = Config {
+            kitty_image_id: Some(7),
+            ..Default::default()
+        };
+
+        let mut vec = Vec::new();
+        let layout = Layout::new(&img, &config).unwrap();
+        print_remote(
+            &TestKeys::new(&[]),
+            &mut vec,
+            &Payload::new(&img, &layout),
+            &layout,
+            &config,
+        )
+        .unwrap();
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        assert!(result.starts_with("\x1b[1;1H\x1b_Gf=32,a=T,t=d,s=2,v=2,c=2,r=1,i=7,q=2,m=1;"));
+    }
+
+    #[test]
+    fn test_print_remote_chunks() {
+        // 40x40 RGB pixels are 4800 bytes, which are sent as they are in two chunks
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(40, 40, |x, y| {
+            image::Rgb([x as u8, y as u8, (x * y) as u8])
+        }));
+        let config = Config::default();
+        let layout = Layout::new(&img, &config).unwrap();
+        let mut vec = Vec::new();
+        write_remote(&mut vec, &Payload::new(&img, &layout), &layout, &config).unwrap();
+
+        // the whole payload encoded at once and split every 4096 characters
+        let encoded = general_purpose::STANDARD.encode(img.as_bytes());
+        let (first, second) = encoded.split_at(4096);
+        assert_eq!(
+            std::str::from_utf8(&vec).unwrap(),
+            format!(
+                "\x1b_Gf=24,a=T,t=d,s=40,v=40,c=40,r=20,m=1;{first}\x1b\\\
+                 \x1b_Gm=0;{second}\x1b\\"
+            )
+        );
+    }
+
+    #[test]
+    fn test_payload_cropped() {
+        let rgb = image::RgbImage::from_fn(4, 3, |x, y| image::Rgb([x as u8, y as u8, 9]));
+        let config = Config {
+            source_rect: Some(crate::Rect::new(1, 1, 2, 2)),
+            ..Default::default()
+        };
+
+        // the whole image is borrowed, and the terminal crops it
+        let img = DynamicImage::ImageRgb8(rgb.clone());
+        let layout = Layout::new(&img, &config).unwrap();
+        let payload = Payload::new(&img, &layout);
+        assert_eq!((payload.format, payload.width, payload.height), (24, 4, 3));
+        assert!(matches!(payload.data, Cow::Borrowed(_)));
+        assert_eq!(payload.source_rect(), ",x=1,y=1,w=2,h=2");
+
+        // other formats are converted
+        let img = DynamicImage::ImageRgb16(DynamicImage::ImageRgb8(rgb).to_rgb16());
+        let payload = Payload::new(&img, &layout);
+        assert_eq!((payload.format, payload.width, payload.height), (32, 4, 3));
+        assert_eq!(&payload.data[..], img.to_rgba8().as_raw().as_slice());
+
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+        let layout = Layout::new(&img, &Config::default()).unwrap();
+        assert_eq!(Payload::new(&img, &layout).source_rect(), "");
+    }
+
+    // Encode the whole payload at once and split it into chunks of 4096 characters
+    fn write_remote_at_once(img: &DynamicImage, layout: &Layout) -> String {
+        let encoded = general_purpose::STANDARD.encode(img.to_rgba8().as_raw());
+        let mut iter = encoded.chars().peekable();
+        let first_chunk: String = iter.by_ref().take(4096).collect();
+        let mut result = format!(
+            "\x1b_Gf=32,a=T,t=d,s={},v={},c={},r={},m=1;{}\x1b\\",
+            img.width(),
+            img.height(),
+            layout.width,
+            layout.height,
+            first_chunk
+        );
+        while iter.peek().is_some() {
+            let chunk: String = iter.by_ref().take(4096).collect();
+            let m = if iter.peek().is_some() { 1 } else { 0 };
+            result += &format!("\x1b_Gm={};{}\x1b\\", m, chunk);
+        }
+        result
+    }
+
+    #[test]
+    fn test_print_remote_chunk_boundaries() {
+        // sizes around one chunk of 3072 bytes and one batch of 64 chunks
+        for (width, height) in [(1, 1), (32, 24), (1, 769), (256, 192), (257, 192)] {
+            let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+                image::Rgba([x as u8, y as u8, (x ^ y) as u8, 255])
+            }));
+            let config = Config {
+                width: Some(Size::Cells(10)),
+                ..Default::default()
+            };
+            let layout = Layout::new(&img, &config).unwrap();
+            let mut vec = Vec::new();
+            write_remote(&mut vec, &Payload::new(&img, &layout), &layout, &config).unwrap();
+            assert_eq!(
+                std::str::from_utf8(&vec).unwrap(),
+                write_remote_at_once(&img, &layout)
+            );
+        }
+    }
+
+    #[test]
+    fn test_print_remote_clip() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(40, 40));
+
+        let config = Config {
+            x: 70,
+            y: 20,
+            width: Some(Size::Cells(20)),
+            height: Some(Size::Cells(10)),
+            fit: crate::Fit::Fill,
+            overflow: crate::Overflow::Clip,
+            ..Default::default()
+        };
+
+        let mut vec = Vec::new();
+        let layout = Layout::new(&img, &config).unwrap();
+        print_remote(
+            &TestKeys::new(&[]),
+            &mut vec,
+            &Payload::new(&img, &layout),
+            &layout,
+            &config,
+        )
+        .unwrap();
+        let result = std::str::from_utf8(&vec).unwrap();
+
+        // only the visible part is displayed, from the same data
+        assert!(result.contains("s=40,v=40,c=10,r=3,x=0,y=0,w=20,h=12,m=1;"));
+    }
+
     #[test]
     fn test_kitty_supported_remote_and_local() {
         // output collected on kitty 0.42.2
@@ -589,4 +1091,70 @@ mod tests {
         assert_eq!(result, "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c");
         assert!(test_response.reached_end());
     }
+
+    #[cfg(feature = "print-file")]
+    #[test]
+    fn test_write_remote_png() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::new(4, 2));
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let config = Config::default();
+        let layout = Layout::new(&img, &config).unwrap();
+
+        let mut vec = Vec::new();
+        write_remote(&mut vec, &Payload::png(&png, &layout), &layout, &config).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&vec).unwrap(),
+            format!(
+                "\x1b_Gf=100,a=T,t=d,s=4,v=2,c=4,r=1,m=1;{}\x1b\\",
+                general_purpose::STANDARD.encode(&png)
+            )
+        );
+    }
+
+    #[test]
+    fn test_print_remote_stored() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            4,
+            4,
+            image::Rgba([1, 2, 3, 4]),
+        ));
+        let print = |source_rect| {
+            let config = Config {
+                source_rect,
+                kitty_image_id: Some(0x7374),
+                ..Default::default()
+            };
+            let layout = Layout::new(&img, &config).unwrap();
+            let mut vec = Vec::new();
+            let payload = Payload::new(&img, &layout);
+            print_remote(&TestKeys::new(&[]), &mut vec, &payload, &layout, &config).unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+
+        assert!(print(None).contains("a=T,t=d,s=4,v=4,c=4,r=2,i=29556,q=2,m=1;"));
+        // only the source rectangle changes, so the stored image is placed again
+        assert_eq!(
+            print(Some(crate::Rect::new(1, 2, 2, 2))),
+            "\x1b[1;1H\x1b_Ga=d,d=i,i=29556,q=2\x1b\\\
+             \x1b_Ga=p,c=2,r=1,x=1,y=2,w=2,h=2,i=29556,q=2\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_write_encoded_forgets_its_id() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4));
+        let config = Config {
+            kitty_image_id: Some(0x7375),
+            ..Default::default()
+        };
+        let encoded = encode(&Source::new(&img, &config).unwrap(), &config).unwrap();
+        stored_images().insert(0x7375, 1);
+        stored_images().insert(0x7376, 2);
+
+        write_encoded(&mut Vec::new(), &encoded, &config).unwrap();
+        assert_eq!(stored_images().get(&0x7375), None);
+        assert_eq!(stored_images().get(&0x7376), Some(&2));
+    }
 }
@@ -0,0 +1,797 @@
+use crate::config::{Config, Fit, Overflow, Rect, Upscale, UpscaleFilter};
+use crate::error::{ViuError, ViuResult};
+use crate::printer::decoration::Decoration;
+use crate::printer::find_best_fit_in;
+use crate::printer::resample::{self, Resampling};
+use crate::utils::cell_size;
+use image::{imageops, imageops::FilterType, DynamicImage, GenericImageView};
+use std::borrow::Cow;
+use std::io::Write;
+
+/// Where and how big an image is printed, in accordance with the [Config].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layout {
+    /// Region of the source image that is printed, in pixels: x, y, width, height.
+    pub crop: (u32, u32, u32, u32),
+    /// Size of the printed image, in terminal cells.
+    pub width: u32,
+    pub height: u32,
+    /// Position of the printed image within the box, in terminal cells.
+    pub offset_x: u32,
+    pub offset_y: u32,
+    /// Size of the area taken up by the image, in terminal cells.
+    pub box_width: u32,
+    pub box_height: u32,
+    /// Position of the box relative to the offsets in the config, according to its alignment.
+    pub box_x: u32,
+    pub box_y: u32,
+    /// Whether the aspect ratio of the image is ignored.
+    pub stretch: bool,
+}
+
+impl Layout {
+    pub(crate) fn new(img: &DynamicImage, config: &Config) -> ViuResult<Self> {
+        Self::with_size(img.width(), img.height(), config)
+    }
+
+    /// Like [Layout::new], for an image of the given size in pixels.
+    pub(crate) fn with_size(width: u32, height: u32, config: &Config) -> ViuResult<Self> {
+        let (left, top, right, bottom) = Decoration::new(config).insets();
+        let space = Space::new(config, (left + right, top + bottom));
+
+        let mut layout = Self::fit((width, height), config, &space)?;
+        layout.offset_x += left;
+        layout.offset_y += top;
+        layout.box_width += left + right;
+        layout.box_height += top + bottom;
+
+        (layout.box_x, layout.box_y) = alignment(layout.box_width, layout.box_height, config);
+        if config.overflow == Overflow::Clip {
+            layout.clip(config)?;
+        }
+        Ok(layout)
+    }
+
+    /// Size the image according to the fit mode, ignoring where it ends up.
+    fn fit(size: (u32, u32), config: &Config, space: &Space) -> ViuResult<Self> {
+        let (src_x, src_y, img_width, img_height) = source_rect(size, config)?;
+        let full = (src_x, src_y, img_width, img_height);
+
+        if config.fit == Fit::Auto {
+            let (w, h) = upscale(img_width, img_height, space, config).unwrap_or_else(|| {
+                let terminal = (space.term_width, space.term_height);
+                find_best_fit_in(img_width, img_height, space.width, space.height, terminal)
+            });
+            return Ok(Self {
+                crop: full,
+                width: w,
+                height: h,
+                offset_x: 0,
+                offset_y: 0,
+                box_width: w,
+                box_height: h,
+                box_x: 0,
+                box_y: 0,
+                stretch: false,
+            });
+        }
+
+        let (box_width, box_height) = space.box_size();
+        let fits = img_width <= box_width && img_height <= 2 * box_height;
+
+        let (crop, width, height) = match config.fit {
+            Fit::Fill => ((0, 0, img_width, img_height), box_width, box_height),
+            Fit::Cover => cover(img_width, img_height, box_width, box_height, config),
+            Fit::None => natural(img_width, img_height, box_width, box_height, config),
+            Fit::ScaleDown if fits => natural(img_width, img_height, box_width, box_height, config),
+            Fit::Auto | Fit::Contain | Fit::ScaleDown => {
+                let (w, h) = contain(img_width, img_height, box_width, box_height);
+                ((0, 0, img_width, img_height), w, h)
+            }
+        };
+        // crops are relative to the source rectangle
+        let crop = (src_x + crop.0, src_y + crop.1, crop.2, crop.3);
+
+        Ok(Self {
+            crop,
+            width,
+            height,
+            offset_x: config.fit_align.horizontal.offset(box_width - width),
+            offset_y: config.fit_align.vertical.offset(box_height - height),
+            box_width,
+            box_height,
+            box_x: 0,
+            box_y: 0,
+            stretch: config.fit == Fit::Fill,
+        })
+    }
+
+    /// Shrink the box and the image so that they end within the terminal, cropping the image
+    /// accordingly. Vertically, this is only possible with an absolute offset.
+    fn clip(&mut self, config: &Config) -> ViuResult {
+        let (term_w, term_h) = config.terminal_size();
+        let left = config.x as u32 + self.box_x;
+        let box_width = std::cmp::min(self.box_width, (term_w as u32).saturating_sub(left));
+        let box_height = match config.absolute_offset {
+            // leave a line for the prompt, so that the final newline does not scroll
+            true => {
+                let top = std::cmp::max(0, config.y) as u32 + self.box_y;
+                let rows = (term_h as u32).saturating_sub(1).saturating_sub(top);
+                std::cmp::min(self.box_height, rows)
+            }
+            false => self.box_height,
+        };
+
+        let width = std::cmp::min(self.width, box_width.saturating_sub(self.offset_x));
+        let height = std::cmp::min(self.height, box_height.saturating_sub(self.offset_y));
+        if width == 0 || height == 0 {
+            return Err(ViuError::InvalidConfiguration(
+                "the image is entirely outside of the terminal".to_owned(),
+            ));
+        }
+
+        // keep the part of the source image that covers the visible cells
+        let visible = |len: u32, cells: u32, total: u32| match cells == total {
+            true => len,
+            false => std::cmp::max(1, (len as u64 * cells as u64 / total as u64) as u32),
+        };
+        let (x, y, w, h) = self.crop;
+        self.crop = (
+            x,
+            y,
+            visible(w, width, self.width),
+            visible(h, height, self.height),
+        );
+        (self.width, self.height) = (width, height);
+        (self.box_width, self.box_height) = (box_width, box_height);
+        Ok(())
+    }
+
+    /// Return the part of the image that should be printed.
+    pub(crate) fn crop<'a>(&self, img: &'a DynamicImage) -> Cow<'a, DynamicImage> {
+        let (x, y, w, h) = self.crop;
+        if self.is_cropped(img) {
+            Cow::Owned(img.crop_imm(x, y, w, h))
+        } else {
+            Cow::Borrowed(img)
+        }
+    }
+
+    pub(crate) fn is_cropped(&self, img: &DynamicImage) -> bool {
+        self.crop != (0, 0, img.width(), img.height())
+    }
+
+    /// Resampling for resizing the cropped image to `width`x`height` pixels. The configured
+    /// filter is used, with `default` in place of [Filter::Auto](crate::Filter::Auto), unless
+    /// the image is enlarged and the config asks for nearest neighbour.
+    pub(crate) fn resampling(
+        &self,
+        width: u32,
+        height: u32,
+        config: &Config,
+        default: FilterType,
+    ) -> Resampling {
+        let (_, _, crop_width, crop_height) = self.crop;
+        let enlarged = width > crop_width || height > crop_height;
+        if enlarged && config.upscale_filter == UpscaleFilter::Nearest {
+            Resampling::Filter(FilterType::Nearest)
+        } else {
+            Resampling::new(config.filter, default)
+        }
+    }
+
+    /// Crop the image and resize it to `width`x`height` pixels, see [Layout::resampling].
+    pub(crate) fn resize(
+        &self,
+        img: &DynamicImage,
+        width: u32,
+        height: u32,
+        config: &Config,
+        default: FilterType,
+    ) -> DynamicImage {
+        let resampling = self.resampling(width, height, config, default);
+        let (x, y, w, h) = self.crop;
+        if let Resampling::Filter(filter) = resampling {
+            // resize the visible region of RGB and RGBA images in place, without copying it
+            match img {
+                DynamicImage::ImageRgba8(rgba) if !config.linear_light => {
+                    return imageops::resize(&*rgba.view(x, y, w, h), width, height, filter).into()
+                }
+                DynamicImage::ImageRgb8(rgb) if !config.linear_light => {
+                    return imageops::resize(&*rgb.view(x, y, w, h), width, height, filter).into()
+                }
+                _ => {}
+            }
+        }
+        resample::resize(
+            &self.crop(img),
+            width,
+            height,
+            resampling,
+            config.linear_light,
+        )
+    }
+
+    /// Config with offsets pointing to the top left corner of the image, instead of the box.
+    pub(crate) fn place(&self, config: &Config) -> Config {
+        let x = self.box_x.saturating_add(self.offset_x);
+        let y = self.box_y.saturating_add(self.offset_y);
+        Config {
+            x: config
+                .x
+                .saturating_add(u16::try_from(x).unwrap_or(u16::MAX)),
+            y: config
+                .y
+                .saturating_add(i16::try_from(y).unwrap_or(i16::MAX)),
+            ..config.clone()
+        }
+    }
+
+    /// Draw the decoration around the image, if any, and return the config to print the
+    /// image with. Replaces [Layout::place] right before printing.
+    pub(crate) fn start(&self, stdout: &mut impl Write, config: &Config) -> ViuResult<Config> {
+        let decoration = Decoration::new(config);
+        if decoration.is_empty() {
+            return Ok(self.place(config));
+        }
+        decoration.draw(stdout, self, config)
+    }
+
+    /// Number of empty rows in the box below the image.
+    pub(crate) fn bottom_padding(&self) -> u32 {
+        self.box_height - self.offset_y - self.height
+    }
+
+    /// Move the cursor past the empty rows below the image, if any. Expected to be called
+    /// when the cursor is on the row after the image.
+    pub(crate) fn finish(&self, stdout: &mut impl Write) -> std::io::Result<()> {
+        for _ in 0..self.bottom_padding() {
+            writeln!(stdout)?;
+        }
+        Ok(())
+    }
+}
+
+/// The region of the image to print, validated against its dimensions.
+fn source_rect(size: (u32, u32), config: &Config) -> ViuResult<(u32, u32, u32, u32)> {
+    let (img_width, img_height) = size;
+    let Some(rect) = config.source_rect else {
+        return Ok((0, 0, img_width, img_height));
+    };
+
+    let fits = |start: u32, len: u32, max: u32| {
+        len > 0 && start.checked_add(len).is_some_and(|end| end <= max)
+    };
+    if !fits(rect.x, rect.width, img_width) || !fits(rect.y, rect.height, img_height) {
+        return Err(ViuError::InvalidConfiguration(format!(
+            "source_rect {:?} is empty or not within the {}x{} image",
+            rect, img_width, img_height
+        )));
+    }
+    Ok((rect.x, rect.y, rect.width, rect.height))
+}
+
+/// The area relative sizes and alignment refer to, in terminal cells. Defaults to the terminal,
+/// leaving a line for the prompt.
+fn area(config: &Config) -> Rect {
+    config.align_area.unwrap_or_else(|| {
+        let (term_w, term_h) = config.terminal_size();
+        // some terminals report no rows at all
+        Rect::new(
+            0,
+            0,
+            term_w as u32,
+            std::cmp::max(1, (term_h as u32).saturating_sub(1)),
+        )
+    })
+}
+
+/// Offset of a box of the given size within the configured alignment area, in terminal cells.
+fn alignment(box_width: u32, box_height: u32, config: &Config) -> (u32, u32) {
+    let Some(align) = config.align else {
+        return (0, 0);
+    };
+    let area = area(config);
+    let free_width = area.width.saturating_sub(box_width);
+    let free_height = area.height.saturating_sub(box_height);
+    (
+        area.x + align.horizontal.offset(free_width),
+        area.y + align.vertical.offset(free_height),
+    )
+}
+
+/// Space available to the image, in terminal cells, without its decoration.
+struct Space {
+    /// The configured width and height.
+    width: Option<u32>,
+    height: Option<u32>,
+    /// Size of the terminal.
+    term_width: u32,
+    term_height: u32,
+}
+
+impl Space {
+    /// Resolve the configured size, taking away `inset` cells horizontally and vertically.
+    fn new(config: &Config, inset: (u32, u32)) -> Self {
+        let (area, (cell_w, cell_h)) = (area(config), cell_size());
+        let (term_w, term_h) = config.terminal_size();
+        let shrink = |cells: u32, by: u32| std::cmp::max(1, cells.saturating_sub(by));
+        Self {
+            width: config
+                .width
+                .map(|w| shrink(w.cells(area.width, cell_w as u32), inset.0)),
+            height: config
+                .height
+                .map(|h| shrink(h.cells(area.height, cell_h as u32), inset.1)),
+            term_width: shrink(term_w as u32, inset.0),
+            term_height: shrink(term_h as u32, inset.1),
+        }
+    }
+
+    /// The size of the box the image should fit in. Missing dimensions are taken from the
+    /// terminal, leaving a line for the prompt.
+    fn box_size(&self) -> (u32, u32) {
+        let width = self.width.unwrap_or(self.term_width);
+        let height = self
+            .height
+            .unwrap_or_else(|| std::cmp::max(1, self.term_height - 1));
+        (std::cmp::max(1, width), std::cmp::max(1, height))
+    }
+}
+
+/// Scale up an image that is smaller than the bounds from the config, according to its
+/// upscaling policy. Returns `None` if the image should not be scaled up.
+fn upscale(width: u32, height: u32, space: &Space, config: &Config) -> Option<(u32, u32)> {
+    // an unbounded dimension never limits the scale
+    const UNBOUNDED: u32 = u32::MAX / 4;
+
+    let (bound_width, bound_height) = match (space.width, space.height) {
+        (None, None) => space.box_size(),
+        (Some(w), None) => (w, UNBOUNDED),
+        (None, Some(h)) => (UNBOUNDED, h),
+        // the image is stretched anyway
+        (Some(_), Some(_)) => return None,
+    };
+
+    if width > bound_width || height > 2 * bound_height {
+        return None;
+    }
+
+    match config.upscale {
+        Upscale::Never => None,
+        Upscale::Always => Some(contain(width, height, bound_width, bound_height)),
+        Upscale::Integer => {
+            let factor = std::cmp::min(bound_width / width, 2 * bound_height / height);
+            let (w, h) = (width * factor, height * factor);
+            (factor > 1).then_some((w, h / 2 + h % 2))
+        }
+    }
+}
+
+/// Largest size that fits in the box while preserving the aspect ratio.
+fn contain(width: u32, height: u32, box_width: u32, box_height: u32) -> (u32, u32) {
+    let (width, height) = (width as u64, height as u64);
+    let (box_width, box_height) = (box_width as u64, box_height as u64);
+
+    if width * 2 * box_height <= box_width * height {
+        // height is the limiting dimension
+        let w = width * 2 * box_height / height;
+        (std::cmp::max(1, w) as u32, box_height as u32)
+    } else {
+        let h = height * box_width / width / 2;
+        (box_width as u32, std::cmp::max(1, h) as u32)
+    }
+}
+
+/// Crop the image so that its aspect ratio matches the box, which it then fills entirely.
+fn cover(
+    width: u32,
+    height: u32,
+    box_width: u32,
+    box_height: u32,
+    config: &Config,
+) -> ((u32, u32, u32, u32), u32, u32) {
+    let (w, h) = (width as u64, height as u64);
+    let (bw, bh) = (box_width as u64, 2 * box_height as u64);
+
+    let crop = if w * bh >= bw * h {
+        // the image is wider than the box, cut the sides
+        let crop_width = std::cmp::max(1, bw * h / bh) as u32;
+        let x = config.fit_align.horizontal.offset(width - crop_width);
+        (x, 0, crop_width, height)
+    } else {
+        let crop_height = std::cmp::max(1, bh * w / bw) as u32;
+        let y = config.fit_align.vertical.offset(height - crop_height);
+        (0, y, width, crop_height)
+    };
+
+    (crop, box_width, box_height)
+}
+
+/// Print one pixel per half cell, cropping whatever does not fit in the box.
+fn natural(
+    width: u32,
+    height: u32,
+    box_width: u32,
+    box_height: u32,
+    config: &Config,
+) -> ((u32, u32, u32, u32), u32, u32) {
+    let crop_width = std::cmp::min(width, box_width);
+    let crop_height = std::cmp::min(height, 2 * box_height);
+    let x = config.fit_align.horizontal.offset(width - crop_width);
+    let y = config.fit_align.vertical.offset(height - crop_height);
+
+    (
+        (x, y, crop_width, crop_height),
+        crop_width,
+        crop_height / 2 + crop_height % 2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Align, HorizontalAlign, Size, VerticalAlign};
+
+    fn layout(img_width: u32, img_height: u32, config: &Config) -> Layout {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(img_width, img_height));
+        Layout::new(&img, config).unwrap()
+    }
+
+    fn config(fit: Fit, width: u32, height: u32) -> Config {
+        Config {
+            fit,
+            width: Some(Size::Cells(width)),
+            height: Some(Size::Cells(height)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layout_auto() {
+        let l = layout(600, 499, &Config::default());
+        assert_eq!((l.width, l.height), (57, 23));
+        assert_eq!((l.box_width, l.box_height), (57, 23));
+        assert_eq!(l.crop, (0, 0, 600, 499));
+        assert_eq!(l.bottom_padding(), 0);
+    }
+
+    #[test]
+    fn test_layout_upscale() {
+        let mut conf = Config {
+            width: Some(Size::Cells(40)),
+            ..Default::default()
+        };
+        let l = layout(16, 16, &conf);
+        assert_eq!((l.width, l.height), (16, 8));
+
+        conf.upscale = Upscale::Always;
+        let l = layout(16, 16, &conf);
+        assert_eq!((l.width, l.height), (40, 20));
+        assert_eq!((l.box_width, l.box_height), (40, 20));
+
+        conf.upscale = Upscale::Integer;
+        let l = layout(16, 16, &conf);
+        assert_eq!((l.width, l.height), (32, 16));
+
+        // terminal size (80x24) is used when no bounds are given
+        conf.width = None;
+        let l = layout(16, 15, &conf);
+        assert_eq!((l.width, l.height), (48, 23));
+
+        conf.upscale = Upscale::Always;
+        let l = layout(16, 16, &conf);
+        assert_eq!((l.width, l.height), (46, 23));
+
+        // larger images are only scaled down
+        let l = layout(600, 499, &conf);
+        assert_eq!((l.width, l.height), (57, 23));
+    }
+
+    #[test]
+    fn test_layout_resampling() {
+        let mut conf = Config {
+            width: Some(Size::Cells(40)),
+            upscale: Upscale::Integer,
+            ..Default::default()
+        };
+        let l = layout(16, 16, &conf);
+        let default = FilterType::CatmullRom;
+        assert_eq!(
+            l.resampling(32, 32, &conf, default),
+            Resampling::Filter(FilterType::CatmullRom)
+        );
+
+        conf.filter = crate::Filter::Box;
+        assert_eq!(l.resampling(32, 32, &conf, default), Resampling::Box);
+
+        conf.upscale_filter = UpscaleFilter::Nearest;
+        assert_eq!(
+            l.resampling(32, 32, &conf, default),
+            Resampling::Filter(FilterType::Nearest)
+        );
+        assert_eq!(l.resampling(8, 8, &conf, default), Resampling::Box);
+    }
+
+    #[test]
+    fn test_layout_fill() {
+        let l = layout(10, 10, &config(Fit::Fill, 40, 10));
+        assert_eq!((l.width, l.height), (40, 10));
+        assert_eq!((l.offset_x, l.offset_y), (0, 0));
+        assert!(l.stretch);
+    }
+
+    #[test]
+    fn test_layout_contain() {
+        // upscaled, letterboxed horizontally
+        let l = layout(10, 10, &config(Fit::Contain, 40, 10));
+        assert_eq!((l.width, l.height), (20, 10));
+        assert_eq!((l.offset_x, l.offset_y), (10, 0));
+        assert_eq!((l.box_width, l.box_height), (40, 10));
+
+        // downscaled, letterboxed vertically
+        let mut conf = config(Fit::Contain, 10, 10);
+        conf.fit_align.vertical = VerticalAlign::Bottom;
+        let l = layout(100, 50, &conf);
+        assert_eq!((l.width, l.height), (10, 2));
+        assert_eq!((l.offset_x, l.offset_y), (0, 8));
+        assert_eq!(l.bottom_padding(), 0);
+        assert_eq!(l.crop, (0, 0, 100, 50));
+    }
+
+    #[test]
+    fn test_layout_cover() {
+        let l = layout(100, 100, &config(Fit::Cover, 40, 10));
+        assert_eq!((l.width, l.height), (40, 10));
+        assert_eq!(l.crop, (0, 25, 100, 50));
+
+        let mut conf = config(Fit::Cover, 10, 10);
+        conf.fit_align = Align {
+            horizontal: HorizontalAlign::Left,
+            vertical: VerticalAlign::Top,
+        };
+        let l = layout(100, 50, &conf);
+        assert_eq!((l.width, l.height), (10, 10));
+        assert_eq!(l.crop, (0, 0, 25, 50));
+    }
+
+    #[test]
+    fn test_layout_none() {
+        let l = layout(20, 7, &config(Fit::None, 40, 10));
+        assert_eq!((l.width, l.height), (20, 4));
+        assert_eq!((l.offset_x, l.offset_y), (10, 3));
+        assert_eq!(l.bottom_padding(), 3);
+
+        let l = layout(100, 100, &config(Fit::None, 40, 10));
+        assert_eq!((l.width, l.height), (40, 10));
+        assert_eq!(l.crop, (30, 40, 40, 20));
+    }
+
+    #[test]
+    fn test_layout_scale_down() {
+        let l = layout(20, 8, &config(Fit::ScaleDown, 40, 10));
+        assert_eq!((l.width, l.height), (20, 4));
+        assert_eq!(l.crop, (0, 0, 20, 8));
+
+        let l = layout(100, 100, &config(Fit::ScaleDown, 40, 10));
+        assert_eq!((l.width, l.height), (20, 10));
+        assert_eq!(l.crop, (0, 0, 100, 100));
+    }
+
+    #[test]
+    fn test_layout_source_rect() {
+        let conf = Config {
+            source_rect: Some(crate::Rect::new(10, 20, 40, 40)),
+            width: Some(Size::Cells(20)),
+            ..Default::default()
+        };
+        let l = layout(100, 100, &conf);
+        assert_eq!((l.width, l.height), (20, 10));
+        assert_eq!(l.crop, (10, 20, 40, 40));
+
+        // crops from fit modes are relative to the source rectangle
+        let conf = Config {
+            source_rect: Some(crate::Rect::new(10, 20, 40, 20)),
+            ..config(Fit::Cover, 10, 10)
+        };
+        let l = layout(100, 100, &conf);
+        assert_eq!(l.crop, (25, 20, 10, 20));
+    }
+
+    #[test]
+    fn test_layout_resize_view() {
+        let conf = Config {
+            source_rect: Some(crate::Rect::new(3, 5, 20, 16)),
+            width: Some(Size::Cells(7)),
+            ..Default::default()
+        };
+        let rgba = image::RgbaImage::from_fn(30, 30, |x, y| {
+            image::Rgba([x as u8 * 8, y as u8 * 8, 0, 255])
+        });
+        let images = [
+            DynamicImage::ImageRgba8(rgba.clone()),
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8()),
+        ];
+        for img in images {
+            let l = Layout::new(&img, &conf).unwrap();
+            let resized = l.resize(&img, 7, 6, &conf, FilterType::CatmullRom);
+            let copied = img
+                .crop_imm(3, 5, 20, 16)
+                .resize_exact(7, 6, FilterType::CatmullRom);
+            assert_eq!(resized, copied);
+        }
+    }
+
+    #[test]
+    fn test_layout_invalid_source_rect() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(10, 10));
+        for rect in [
+            crate::Rect::new(0, 0, 0, 5),
+            crate::Rect::new(5, 0, 6, 5),
+            crate::Rect::new(0, u32::MAX, 1, 1),
+        ] {
+            let conf = Config {
+                source_rect: Some(rect),
+                ..Default::default()
+            };
+            assert!(matches!(
+                Layout::new(&img, &conf),
+                Err(ViuError::InvalidConfiguration(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_place_align() {
+        let l = layout(20, 8, &config(Fit::Contain, 10, 4));
+        assert_eq!((l.box_width, l.box_height), (10, 4));
+        assert_eq!((l.offset_x, l.offset_y), (0, 1));
+
+        // the default area is the terminal, without its last line
+        let conf = Config {
+            align: Some(Align::default()),
+            ..config(Fit::Contain, 10, 4)
+        };
+        let placed = layout(20, 8, &conf).place(&conf);
+        assert_eq!((placed.x, placed.y), (35, 9 + 1));
+
+        let conf = Config {
+            x: 1,
+            y: 2,
+            align: Some(Align {
+                horizontal: HorizontalAlign::Right,
+                vertical: VerticalAlign::Bottom,
+            }),
+            align_area: Some(Rect::new(5, 6, 30, 10)),
+            ..config(Fit::Contain, 10, 4)
+        };
+        let placed = layout(20, 8, &conf).place(&conf);
+        assert_eq!((placed.x, placed.y), (1 + 5 + 20, 2 + 6 + 6 + 1));
+
+        // areas smaller than the box keep it at their corner
+        let conf = Config {
+            align_area: Some(Rect::new(3, 0, 5, 2)),
+            ..conf
+        };
+        let placed = layout(20, 8, &conf).place(&conf);
+        assert_eq!((placed.x, placed.y), (1 + 3, 2 + 1));
+    }
+
+    #[test]
+    fn test_layout_relative_size() {
+        // 50% of the 80x24 terminal, without the prompt line
+        let conf = Config {
+            width: Some(Size::Percent(50)),
+            height: Some(Size::Percent(50)),
+            fit: Fit::Fill,
+            ..Default::default()
+        };
+        let l = layout(10, 10, &conf);
+        assert_eq!((l.width, l.height), (40, 11));
+
+        // percentages of the alignment area
+        let conf = Config {
+            align_area: Some(Rect::new(0, 0, 30, 10)),
+            ..conf
+        };
+        let l = layout(10, 10, &conf);
+        assert_eq!((l.width, l.height), (15, 5));
+
+        // cells are 10x20 pixels in tests
+        let conf = Config {
+            width: Some(Size::Pixels(200)),
+            height: Some(Size::Pixels(5)),
+            ..conf
+        };
+        let l = layout(10, 10, &conf);
+        assert_eq!((l.width, l.height), (20, 1));
+
+        let conf = Config {
+            width: Some(Size::Percent(25)),
+            ..Default::default()
+        };
+        let l = layout(100, 100, &conf);
+        assert_eq!((l.width, l.height), (20, 10));
+    }
+
+    #[test]
+    fn test_layout_clip() {
+        let conf = Config {
+            x: 70,
+            y: 20,
+            overflow: Overflow::Clip,
+            ..config(Fit::Fill, 20, 10)
+        };
+        // only 10 columns and 3 rows are left, with the last line kept free
+        let l = layout(40, 40, &conf);
+        assert_eq!((l.width, l.height), (10, 3));
+        assert_eq!((l.box_width, l.box_height), (10, 3));
+        assert_eq!(l.crop, (0, 0, 20, 12));
+
+        // the image is clipped within its box
+        let conf = Config {
+            x: 70,
+            ..config(Fit::Contain, 20, 10)
+        };
+        let clip = Config {
+            overflow: Overflow::Clip,
+            ..conf.clone()
+        };
+        let l = layout(10, 20, &clip);
+        assert_eq!((l.offset_x, l.width, l.box_width), (5, 5, 10));
+        assert_eq!(l.crop, (0, 0, 5, 20));
+
+        // scrolling leaves everything as it is
+        let l = layout(10, 20, &conf);
+        assert_eq!((l.width, l.box_width), (10, 20));
+
+        // the bottom edge is unknown with relative offsets
+        let conf = Config {
+            y: 20,
+            absolute_offset: false,
+            overflow: Overflow::Clip,
+            ..config(Fit::Fill, 20, 10)
+        };
+        assert_eq!(layout(40, 40, &conf).height, 10);
+    }
+
+    #[test]
+    fn test_layout_clip_outside() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(10, 10));
+        let conf = Config {
+            x: 80,
+            overflow: Overflow::Clip,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Layout::new(&img, &conf),
+            Err(ViuError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[test]
+    fn test_layout_no_rows() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(10, 10));
+        let conf = Config {
+            align: Some(Align::default()),
+            terminal_size: Some((80, 0)),
+            ..Default::default()
+        };
+        assert_eq!(area(&conf), Rect::new(0, 0, 80, 1));
+        let l = Layout::new(&img, &conf).unwrap();
+        assert_eq!(l.box_height, 1);
+    }
+
+    #[test]
+    fn test_place_saturates() {
+        // the image is one row below the top of the box
+        let l = layout(20, 8, &config(Fit::Contain, 10, 4));
+        let conf = Config {
+            x: u16::MAX,
+            y: i16::MAX,
+            ..Default::default()
+        };
+        let placed = l.place(&conf);
+        assert_eq!((placed.x, placed.y), (u16::MAX, i16::MAX));
+    }
+}
@@ -3,16 +3,30 @@ use crate::error::{ViuError, ViuResult};
 use crate::utils::terminal_size;
 use crossterm::cursor::{MoveRight, MoveTo, MoveToPreviousLine};
 use crossterm::execute;
-use image::{DynamicImage, GenericImageView};
+use crossterm::terminal::{Clear, ClearType};
+use image::{imageops::FilterType, DynamicImage};
+use layout::Layout;
 use std::io::Write;
 
 #[cfg(feature = "print-file")]
-use std::path::Path;
+use std::{io::Cursor, path::Path};
+
+mod backdrop;
+mod decoration;
+pub(crate) mod layout;
+pub(crate) mod orientation;
+mod parallel;
+mod resample;
+mod source;
+pub(crate) use source::Source;
 
 mod block;
-pub use block::BlockPrinter;
+#[cfg(test)]
+pub(crate) use block::AnsiBlockPrinter;
+pub use block::{BlockPrinter, BlockRenderer};
 
 mod kitty;
+pub(crate) use kitty::DEFAULT_KITTY_IMAGE_ID;
 pub use kitty::{get_kitty_support, KittyPrinter, KittySupport};
 
 #[cfg(all(feature = "sixel", not(windows)))]
@@ -38,7 +52,7 @@ pub use iterm::iTermPrinter;
 pub use iterm::is_iterm_supported;
 #[cfg(test)]
 use read_key::test_utils::TestKeys;
-use read_key::ReadKey;
+pub(crate) use read_key::ReadKey;
 
 pub trait Printer {
     // Print the given image in the terminal while respecting the options in the config struct.
@@ -51,6 +65,17 @@ pub trait Printer {
         stdout: &mut impl Write,
         img: &DynamicImage,
         config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        self.print_source(stdin, stdout, &Source::new(img, config)?, config)
+    }
+
+    // Print an image which is already oriented and laid out, see [Source].
+    fn print_source(
+        &self,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        source: &Source,
+        config: &Config,
     ) -> ViuResult<(u32, u32)>;
 
     #[cfg(feature = "print-file")]
@@ -61,11 +86,28 @@ pub trait Printer {
         filename: P,
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
-        let img = image::ImageReader::open(filename)?
-            .with_guessed_format()?
-            .decode()?;
+        let (img, _) = orientation::decode(image::ImageReader::open(filename)?, config)?;
         self.print(stdin, stdout, &img, config)
     }
+
+    // Print an encoded image, guessing its format from the content.
+    #[cfg(feature = "print-file")]
+    fn print_from_bytes(
+        &self,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        bytes: &[u8],
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        let (img, _) = orientation::decode(image::ImageReader::new(Cursor::new(bytes)), config)?;
+        self.print(stdin, stdout, &img, config)
+    }
+
+    // Remove everything from the screen, including images the terminal keeps track of.
+    fn clear(&self, stdout: &mut impl Write) -> ViuResult {
+        execute!(stdout, Clear(ClearType::All))?;
+        Ok(())
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -81,21 +123,21 @@ pub enum PrinterType {
 }
 
 impl Printer for PrinterType {
-    fn print(
+    fn print_source(
         &self,
         stdin: &impl ReadKey,
         stdout: &mut impl Write,
-        img: &DynamicImage,
+        source: &Source,
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
         match self {
-            PrinterType::Block => BlockPrinter.print(stdin, stdout, img, config),
-            PrinterType::Kitty => KittyPrinter.print(stdin, stdout, img, config),
-            PrinterType::iTerm => iTermPrinter.print(stdin, stdout, img, config),
+            PrinterType::Block => BlockPrinter.print_source(stdin, stdout, source, config),
+            PrinterType::Kitty => KittyPrinter.print_source(stdin, stdout, source, config),
+            PrinterType::iTerm => iTermPrinter.print_source(stdin, stdout, source, config),
             #[cfg(all(feature = "sixel", not(windows)))]
-            PrinterType::Sixel => SixelPrinter.print(stdin, stdout, img, config),
+            PrinterType::Sixel => SixelPrinter.print_source(stdin, stdout, source, config),
             #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
-            PrinterType::IcySixel => IcySixelPrinter.print(stdin, stdout, img, config),
+            PrinterType::IcySixel => IcySixelPrinter.print_source(stdin, stdout, source, config),
         }
     }
 
@@ -119,12 +161,86 @@ impl Printer for PrinterType {
             }
         }
     }
+
+    #[cfg(feature = "print-file")]
+    fn print_from_bytes(
+        &self,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        bytes: &[u8],
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        match self {
+            PrinterType::Block => BlockPrinter.print_from_bytes(stdin, stdout, bytes, config),
+            PrinterType::Kitty => KittyPrinter.print_from_bytes(stdin, stdout, bytes, config),
+            PrinterType::iTerm => iTermPrinter.print_from_bytes(stdin, stdout, bytes, config),
+            #[cfg(all(feature = "sixel", not(windows)))]
+            PrinterType::Sixel => SixelPrinter.print_from_bytes(stdin, stdout, bytes, config),
+            #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
+            PrinterType::IcySixel => IcySixelPrinter.print_from_bytes(stdin, stdout, bytes, config),
+        }
+    }
+
+    fn clear(&self, stdout: &mut impl Write) -> ViuResult {
+        match self {
+            PrinterType::Block => BlockPrinter.clear(stdout),
+            PrinterType::Kitty => KittyPrinter.clear(stdout),
+            PrinterType::iTerm => iTermPrinter.clear(stdout),
+            #[cfg(all(feature = "sixel", not(windows)))]
+            PrinterType::Sixel => SixelPrinter.clear(stdout),
+            #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
+            PrinterType::IcySixel => IcySixelPrinter.clear(stdout),
+        }
+    }
+}
+
+/// An image encoded by a printer, which can be printed many times. See [crate::PreparedImage].
+#[derive(Debug, Clone)]
+pub(crate) struct Encoded {
+    pub layout: Layout,
+    /// The encoded image, in one piece or one per row of cells.
+    pub data: Vec<Vec<u8>>,
+}
+
+impl PrinterType {
+    /// Do the expensive part of printing: orienting, resizing and encoding the image.
+    pub(crate) fn encode(&self, img: &DynamicImage, config: &Config) -> ViuResult<Encoded> {
+        let source = &Source::new(img, config)?;
+        match self {
+            PrinterType::Block => block::encode(source, config),
+            PrinterType::Kitty => kitty::encode(source, config),
+            PrinterType::iTerm => iterm::encode(source),
+            #[cfg(all(feature = "sixel", not(windows)))]
+            PrinterType::Sixel => self::sixel::encode(source, config),
+            #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
+            PrinterType::IcySixel => icy_sixel::encode(source, config),
+        }
+    }
+
+    /// Print an image returned by [PrinterType::encode], at the offsets in the config.
+    pub(crate) fn write_encoded(
+        &self,
+        stdout: &mut impl Write,
+        encoded: &Encoded,
+        config: &Config,
+    ) -> ViuResult<(u32, u32)> {
+        match self {
+            PrinterType::Block => block::write_encoded(stdout, encoded, config),
+            PrinterType::Kitty => kitty::write_encoded(stdout, encoded, config),
+            PrinterType::iTerm => iterm::write_encoded(stdout, encoded, config),
+            #[cfg(all(feature = "sixel", not(windows)))]
+            PrinterType::Sixel => self::sixel::write_encoded(stdout, encoded, config),
+            #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
+            PrinterType::IcySixel => icy_sixel::write_encoded(stdout, encoded, config),
+        }
+    }
 }
 
 /// Resize a [image::DynamicImage] so that it fits within optional width and height bounds.
-/// If none are provided, terminal size is used instead.
+/// If none are provided, terminal size is used instead. Images are never enlarged, see
+/// [resize_with_config] for that.
 pub fn resize(img: &DynamicImage, width: Option<u32>, height: Option<u32>) -> DynamicImage {
-    let (w, h) = find_best_fit(img, width, height);
+    let (w, h) = find_best_fit(img.width(), img.height(), width, height);
 
     // find_best_fit returns values in terminal cells. Hence, we multiply by two
     // because a 5x10 image can fit in 5x5 cells. However, a 5x9 image will also
@@ -150,24 +266,41 @@ pub fn resize(img: &DynamicImage, width: Option<u32>, height: Option<u32>) -> Dy
 /// The best fit would be to use the whole width (80) and 40 vertical squares,
 /// which is equivalent to 20 terminal cells.
 ///
-/// let img = image::DynamicImage::ImageRgba8(image::RgbaImage::new(160, 80));
-/// let (w, h) = find_best_fit(&img, None, None);
+/// let (w, h) = find_best_fit(160, 80, None, None);
 /// assert_eq!(w, 80);
 /// assert_eq!(h, 20);
-//TODO: it might make more sense to change signiture from img to (width, height)
-fn find_best_fit(img: &DynamicImage, width: Option<u32>, height: Option<u32>) -> (u32, u32) {
-    let (img_width, img_height) = img.dimensions();
+fn find_best_fit(
+    img_width: u32,
+    img_height: u32,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> (u32, u32) {
+    let (term_w, term_h) = terminal_size();
+    let terminal = (term_w as u32, term_h as u32);
+    find_best_fit_in(img_width, img_height, width, height, terminal)
+}
 
+/// Same as [find_best_fit], but with `terminal` in place of the actual terminal size.
+fn find_best_fit_in(
+    img_width: u32,
+    img_height: u32,
+    width: Option<u32>,
+    height: Option<u32>,
+    (term_w, term_h): (u32, u32),
+) -> (u32, u32) {
     // Match user's width and height preferences
     match (width, height) {
         (None, None) => {
-            let (term_w, term_h) = terminal_size();
-            let (w, h) = fit_dimensions(img_width, img_height, term_w as u32, term_h as u32);
+            let (w, h) = fit_dimensions(img_width, img_height, term_w, term_h);
 
             // One less row because two reasons:
             // - the prompt after executing the command will take a line
             // - gifs flicker
-            let h = if h == term_h as u32 { h - 1 } else { h };
+            let h = if h == term_h {
+                std::cmp::max(1, h - 1)
+            } else {
+                h
+            };
             (w, h)
         }
         // Either width or height is specified, will fit and preserve aspect ratio.
@@ -215,13 +348,18 @@ fn fit_dimensions(width: u32, height: u32, bound_width: u32, bound_height: u32)
     }
 }
 
+/// A column or row to move the cursor to with crossterm, which adds one to it in escape codes.
+pub(crate) fn cursor_position(v: u16) -> u16 {
+    std::cmp::min(v, u16::MAX - 1)
+}
+
 // Move the cursor to a location from where it should start printing. Calculations are based on
 // offsets from the config.
 fn adjust_offset(stdout: &mut impl Write, config: &Config) -> ViuResult {
     if config.absolute_offset {
         if config.y >= 0 {
             // If absolute_offset, move to (x,y).
-            execute!(stdout, MoveTo(config.x, config.y as u16))?;
+            execute!(stdout, MoveTo(cursor_position(config.x), config.y as u16))?;
         } else {
             //Negative values do not make sense.
             return Err(ViuError::InvalidConfiguration(
@@ -231,7 +369,7 @@ fn adjust_offset(stdout: &mut impl Write, config: &Config) -> ViuResult {
     } else {
         if config.y < 0 {
             // MoveUp if negative
-            execute!(stdout, MoveToPreviousLine(-config.y as u16))?;
+            execute!(stdout, MoveToPreviousLine(config.y.unsigned_abs()))?;
         } else {
             // Move down y lines
             for _ in 0..config.y {
@@ -249,6 +387,16 @@ fn adjust_offset(stdout: &mut impl Write, config: &Config) -> ViuResult {
     Ok(())
 }
 
+/// Resize a [image::DynamicImage] the way the block printer does, according to the [Config]:
+/// rotated, cropped and fit like when it is printed, including `upscale` and `upscale_filter`.
+/// The result has one pixel per column and two per row of terminal cells.
+pub fn resize_with_config(img: &DynamicImage, config: &Config) -> ViuResult<DynamicImage> {
+    let Source { img, layout } = Source::new(img, config)?;
+    let (_, _, _, crop_height) = layout.crop;
+    let (width, height) = (layout.width, 2 * layout.height - crop_height % 2);
+    Ok(layout.resize(&img, width, height, config, FilterType::CatmullRom))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -275,6 +423,29 @@ mod tests {
         DynamicImage::ImageRgba8(image::RgbaImage::new(20, 10))
     }
 
+    #[test]
+    fn test_resize_with_config_upscale() {
+        let img = resize_get_small_test_image();
+        let mut config = Config {
+            width: Some(crate::Size::Cells(40)),
+            ..Default::default()
+        };
+        let new_img = resize_with_config(&img, &config).unwrap();
+        assert_eq!((new_img.width(), new_img.height()), (20, 10));
+
+        config.upscale = crate::Upscale::Always;
+        let new_img = resize_with_config(&img, &config).unwrap();
+        assert_eq!((new_img.width(), new_img.height()), (40, 20));
+
+        // nearest neighbour keeps the pixels sharp
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 2, |x, _| {
+            image::Rgba([x as u8 * 255, 0, 0, 255])
+        }));
+        config.upscale_filter = crate::UpscaleFilter::Nearest;
+        let new_img = resize_with_config(&img, &config).unwrap().to_rgba8();
+        assert!(new_img.pixels().all(|p| p[0] == 0 || p[0] == 255));
+    }
+
     // Resize tests
 
     #[test]
@@ -350,17 +521,17 @@ mod tests {
         let height = None;
 
         let img = best_fit_large_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 57);
         assert_eq!(h, 23);
 
         let img = best_fit_small_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 40);
         assert_eq!(h, 13);
 
         let img = DynamicImage::ImageRgba8(image::RgbaImage::new(160, 80));
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 80);
         assert_eq!(h, 20);
     }
@@ -371,22 +542,22 @@ mod tests {
         let height = None;
 
         let img = best_fit_large_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 100);
         assert_eq!(h, 41);
 
         let img = best_fit_small_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 40);
         assert_eq!(h, 13);
 
         let width = Some(6);
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 6);
         assert_eq!(h, 1);
 
         let width = Some(3);
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 3);
         assert_eq!(h, 1);
     }
@@ -397,13 +568,13 @@ mod tests {
         let height = Some(90);
 
         let img = best_fit_large_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 216);
         assert_eq!(h, 90);
 
         let height = Some(4);
         let img = best_fit_small_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 12);
         assert_eq!(h, 4);
     }
@@ -414,12 +585,12 @@ mod tests {
         let height = Some(9);
 
         let img = best_fit_large_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 15);
         assert_eq!(h, 9);
 
         let img = best_fit_small_test_image();
-        let (w, h) = find_best_fit(&img, width, height);
+        let (w, h) = find_best_fit(img.width(), img.height(), width, height);
         assert_eq!(w, 15);
         assert_eq!(h, 9);
     }
@@ -491,6 +662,25 @@ mod tests {
         test_adjust_offset_output(&config, "\x1b[2F\x1b[1C");
     }
 
+    #[test]
+    fn test_adjust_offset_limits() {
+        let config = Config {
+            absolute_offset: true,
+            x: u16::MAX,
+            y: i16::MAX,
+            ..Default::default()
+        };
+        test_adjust_offset_output(&config, "\x1b[32768;65535H");
+
+        let config = Config {
+            absolute_offset: false,
+            x: 0,
+            y: i16::MIN,
+            ..Default::default()
+        };
+        test_adjust_offset_output(&config, "\x1b[32768F");
+    }
+
     #[test]
     fn test_invalid_adjust_offset() {
         let config = Config {
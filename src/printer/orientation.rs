@@ -0,0 +1,161 @@
+use crate::config::{Config, Rotation};
+use image::{metadata::Orientation, DynamicImage};
+use std::borrow::Cow;
+
+#[cfg(feature = "print-file")]
+use crate::error::ViuResult;
+#[cfg(feature = "print-file")]
+use crate::printer::layout::Layout;
+#[cfg(feature = "print-file")]
+use image::{ImageDecoder, ImageFormat, ImageReader};
+#[cfg(feature = "print-file")]
+use std::io::{BufRead, Cursor, Seek};
+
+/// Whether the `rotate` and `flip_*` options change the image at all.
+pub(crate) fn is_oriented(config: &Config) -> bool {
+    config.rotate != Rotation::None || config.flip_horizontal || config.flip_vertical
+}
+
+/// Rotate and flip the image as configured, borrowing it if there is nothing to do.
+pub(crate) fn orient<'a>(img: &'a DynamicImage, config: &Config) -> Cow<'a, DynamicImage> {
+    if !is_oriented(config) {
+        return Cow::Borrowed(img);
+    }
+
+    let mut img = match config.rotate {
+        Rotation::None => img.clone(),
+        Rotation::Deg90 => img.rotate90(),
+        Rotation::Deg180 => img.rotate180(),
+        Rotation::Deg270 => img.rotate270(),
+    };
+    if config.flip_horizontal {
+        img.apply_orientation(Orientation::FlipHorizontal);
+    }
+    if config.flip_vertical {
+        img.apply_orientation(Orientation::FlipVertical);
+    }
+    Cow::Owned(img)
+}
+
+/// Decode an image, applying its EXIF orientation unless disabled in the config.
+/// Also returns whether the pixels had to be transformed.
+#[cfg(feature = "print-file")]
+pub(crate) fn decode<R: BufRead + Seek>(
+    reader: ImageReader<R>,
+    config: &Config,
+) -> ViuResult<(DynamicImage, bool)> {
+    let mut decoder = reader.with_guessed_format()?.into_decoder()?;
+    let orientation = match config.exif_orientation {
+        true => decoder.orientation()?,
+        false => Orientation::NoTransforms,
+    };
+
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok((img, orientation != Orientation::NoTransforms))
+}
+
+/// The format and layout of an encoded image, if it can be printed without decoding it,
+/// because it is neither rotated, flipped nor cropped. Only the header is read.
+#[cfg(feature = "print-file")]
+pub(crate) fn passthrough(
+    bytes: &[u8],
+    config: &Config,
+) -> ViuResult<Option<(ImageFormat, Layout)>> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let Some(format) = reader.format() else {
+        return Ok(None);
+    };
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let transformed =
+        config.exif_orientation && decoder.orientation()? != Orientation::NoTransforms;
+    let layout = Layout::with_size(width, height, config)?;
+    if transformed || is_oriented(config) || layout.crop != (0, 0, width, height) {
+        return Ok(None);
+    }
+    Ok(Some((format, layout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    // 2x1 image with a red left and a blue right pixel
+    fn image() -> DynamicImage {
+        let img = RgbaImage::from_fn(2, 1, |x, _| match x {
+            0 => Rgba([255, 0, 0, 255]),
+            _ => Rgba([0, 0, 255, 255]),
+        });
+        DynamicImage::ImageRgba8(img)
+    }
+
+    fn oriented(rotate: Rotation, flip_horizontal: bool, flip_vertical: bool) -> DynamicImage {
+        let config = Config {
+            rotate,
+            flip_horizontal,
+            flip_vertical,
+            ..Default::default()
+        };
+        orient(&image(), &config).into_owned()
+    }
+
+    #[test]
+    fn test_orient_rotate() {
+        assert!(matches!(
+            orient(&image(), &Config::default()),
+            Cow::Borrowed(_)
+        ));
+
+        let img = oriented(Rotation::Deg90, false, false);
+        assert_eq!(img.dimensions(), (1, 2));
+        assert_eq!(img.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+
+        let img = oriented(Rotation::Deg270, false, false);
+        assert_eq!(img.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+
+        let img = oriented(Rotation::Deg180, false, false);
+        assert_eq!(img.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_orient_flip_after_rotation() {
+        let img = oriented(Rotation::Deg90, false, true);
+        assert_eq!(img.dimensions(), (1, 2));
+        assert_eq!(img.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+
+        let img = oriented(Rotation::None, true, false);
+        assert_eq!(img.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+
+        let img = oriented(Rotation::Deg180, true, false);
+        assert_eq!(img.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[cfg(feature = "print-file")]
+    #[test]
+    fn test_passthrough() {
+        let mut png = Vec::new();
+        image()
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let (format, layout) = passthrough(&png, &Config::default()).unwrap().unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        assert_eq!(layout.crop, (0, 0, 2, 1));
+
+        let config = Config {
+            flip_horizontal: true,
+            ..Default::default()
+        };
+        assert!(passthrough(&png, &config).unwrap().is_none());
+        let config = Config {
+            source_rect: Some(crate::Rect::new(0, 0, 1, 1)),
+            ..Default::default()
+        };
+        assert!(passthrough(&png, &config).unwrap().is_none());
+        assert!(passthrough(b"not an image", &Config::default())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,161 @@
+use image::{DynamicImage, ImageBuffer, Pixel, Rgba, RgbaImage};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::borrow::Cow;
+
+// Work is split into rows or chunks, which are processed on all cores with the `rayon` feature
+// and one after another without it. Both ways produce the same result.
+
+/// Call `f` with the index and contents of every row of `data`, each `len` items long.
+pub(crate) fn for_each_row<T, F>(data: &mut [T], len: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Send + Sync,
+{
+    if len == 0 {
+        return;
+    }
+    #[cfg(feature = "rayon")]
+    if !serial() {
+        data.par_chunks_mut(len)
+            .enumerate()
+            .for_each(|(i, row)| f(i, row));
+        return;
+    }
+    data.chunks_mut(len)
+        .enumerate()
+        .for_each(|(i, row)| f(i, row));
+}
+
+#[cfg(all(test, feature = "rayon"))]
+thread_local! {
+    static SERIAL: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+#[cfg(all(feature = "rayon", not(test)))]
+fn serial() -> bool {
+    false
+}
+
+#[cfg(all(feature = "rayon", test))]
+fn serial() -> bool {
+    SERIAL.get()
+}
+
+/// Run `f` with the work of this thread done one row after another, as without the `rayon`
+/// feature, to compare the output of both ways in tests.
+#[cfg(test)]
+pub(crate) fn serially<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "rayon")]
+    SERIAL.set(true);
+    let result = f();
+    #[cfg(feature = "rayon")]
+    SERIAL.set(false);
+    result
+}
+
+/// Like [ImageBuffer::from_fn], computing the pixels row by row.
+pub(crate) fn image_from_fn<P, F>(width: u32, height: u32, f: F) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Send,
+    F: Fn(u32, u32) -> P + Send + Sync,
+{
+    let mut img = ImageBuffer::new(width, height);
+    let channels = P::CHANNEL_COUNT as usize;
+    for_each_row(&mut img, width as usize * channels, |y, row| {
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            pixel.copy_from_slice(f(x as u32, y as u32).channels());
+        }
+    });
+    img
+}
+
+/// Like [DynamicImage::to_rgba8], converting the common 8 bit formats row by row. Images
+/// which already are RGBA are borrowed.
+pub(crate) fn rgba8(img: &DynamicImage) -> Cow<'_, RgbaImage> {
+    let (width, height) = (img.width(), img.height());
+    Cow::Owned(match img {
+        DynamicImage::ImageRgba8(rgba) => return Cow::Borrowed(rgba),
+        DynamicImage::ImageRgb8(rgb) => image_from_fn(width, height, |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            Rgba([r, g, b, u8::MAX])
+        }),
+        DynamicImage::ImageLuma8(luma) => image_from_fn(width, height, |x, y| {
+            let [l] = luma.get_pixel(x, y).0;
+            Rgba([l, l, l, u8::MAX])
+        }),
+        DynamicImage::ImageLumaA8(luma) => image_from_fn(width, height, |x, y| {
+            let [l, a] = luma.get_pixel(x, y).0;
+            Rgba([l, l, l, a])
+        }),
+        _ => img.to_rgba8(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayAlphaImage, GrayImage, LumaA, RgbImage};
+
+    #[test]
+    fn test_image_from_fn() {
+        let f = |x: u32, y: u32| Rgba([x as u8, y as u8, (x * y) as u8, 7]);
+        assert_eq!(image_from_fn(37, 23, f), RgbaImage::from_fn(37, 23, f));
+        assert_eq!(image_from_fn(0, 5, f).dimensions(), (0, 5));
+    }
+
+    #[test]
+    fn test_serial_output() {
+        use crate::config::Filter;
+        use crate::printer::PrinterType;
+        use crate::{Config, Size};
+
+        // large enough for many rows, chunks and batches of Kitty chunks
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(640, 480, |x, y| {
+            Rgba([(x * 3) as u8, (y * 5) as u8, (x ^ y) as u8, (x + y) as u8])
+        }));
+        #[allow(unused_mut)]
+        let mut printers = vec![PrinterType::Block, PrinterType::Kitty];
+        #[cfg(all(feature = "sixel", not(windows)))]
+        printers.push(PrinterType::Sixel);
+        #[cfg(any(feature = "icy_sixel", all(feature = "sixel", windows)))]
+        printers.push(PrinterType::IcySixel);
+
+        for (filter, linear_light) in [(Filter::Auto, false), (Filter::Box, true)] {
+            let config = Config {
+                width: Some(Size::Cells(60)),
+                filter,
+                linear_light,
+                ..Default::default()
+            };
+            for printer in &printers {
+                let encoded = printer.encode(&img, &config).unwrap();
+                let serial = serially(|| printer.encode(&img, &config)).unwrap();
+                assert_eq!(encoded.data, serial.data, "{printer:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_rgba8() {
+        let images = [
+            DynamicImage::ImageRgb8(RgbImage::from_fn(9, 5, |x, y| {
+                image::Rgb([x as u8, y as u8, 3])
+            })),
+            DynamicImage::ImageLuma8(GrayImage::from_fn(9, 5, |x, y| {
+                image::Luma([(x * y) as u8])
+            })),
+            DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(9, 5, |x, y| {
+                LumaA([x as u8, y as u8 * 40])
+            })),
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 4]))),
+            DynamicImage::ImageRgb16(image::ImageBuffer::from_pixel(2, 2, image::Rgb([1000; 3]))),
+        ];
+        for img in images {
+            assert_eq!(*rgba8(&img), img.to_rgba8());
+        }
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(2, 2));
+        assert!(matches!(rgba8(&img), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,421 @@
+use crate::config::Filter;
+use crate::printer::parallel::{for_each_row, image_from_fn, rgba8};
+use image::{
+    imageops::FilterType, DynamicImage, ImageBuffer, Pixel, Rgba, Rgba32FImage, RgbaImage,
+};
+use std::sync::LazyLock;
+
+/// How an image is resampled when resizing, see [Filter].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resampling {
+    /// One of the filters supported by the [image] crate.
+    Filter(FilterType),
+    /// Area averaging.
+    Box,
+}
+
+impl Resampling {
+    /// Resolve the configured filter, using `default` for [Filter::Auto].
+    pub(crate) fn new(filter: Filter, default: FilterType) -> Self {
+        match filter {
+            Filter::Auto => Resampling::Filter(default),
+            Filter::Nearest => Resampling::Filter(FilterType::Nearest),
+            Filter::Box => Resampling::Box,
+            Filter::Triangle => Resampling::Filter(FilterType::Triangle),
+            Filter::CatmullRom => Resampling::Filter(FilterType::CatmullRom),
+            Filter::Gaussian => Resampling::Filter(FilterType::Gaussian),
+            Filter::Lanczos3 => Resampling::Filter(FilterType::Lanczos3),
+        }
+    }
+}
+
+/// Resize the image to exactly `width`x`height` pixels. If `linear_light` is set, the
+/// colors are converted from sRGB to linear light before resampling and back after it.
+pub(crate) fn resize(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    resampling: Resampling,
+    linear_light: bool,
+) -> DynamicImage {
+    match resampling {
+        Resampling::Filter(filter) if !linear_light => resize_exact(img, width, height, filter),
+        Resampling::Filter(filter) => {
+            from_linear(&filter_resize(&to_linear(img), width, height, filter))
+        }
+        Resampling::Box if linear_light => from_linear(&box_resize(&to_linear(img), width, height)),
+        Resampling::Box => {
+            let resized = box_resize(&img.to_rgba32f(), width, height);
+            DynamicImage::ImageRgba32F(resized).to_rgba8().into()
+        }
+    }
+}
+
+/// Like [DynamicImage::resize_exact], resampling the common 8 bit formats row by row.
+fn resize_exact(img: &DynamicImage, width: u32, height: u32, filter: FilterType) -> DynamicImage {
+    match img {
+        DynamicImage::ImageRgba8(img) => filter_resize(img, width, height, filter).into(),
+        DynamicImage::ImageRgb8(img) => filter_resize(img, width, height, filter).into(),
+        DynamicImage::ImageLuma8(img) => filter_resize(img, width, height, filter).into(),
+        DynamicImage::ImageLumaA8(img) => filter_resize(img, width, height, filter).into(),
+        _ => img.resize_exact(width, height, filter),
+    }
+}
+
+/// Subpixels which are resampled in floating point.
+trait Sample: Copy + Send + Sync {
+    fn into_f32(self) -> f32;
+    fn from_f32(v: f32) -> Self;
+}
+
+impl Sample for u8 {
+    fn into_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(v: f32) -> Self {
+        v.clamp(0.0, 255.0).round() as u8
+    }
+}
+
+impl Sample for f32 {
+    fn into_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(v: f32) -> Self {
+        v.clamp(0.0, 1.0)
+    }
+}
+
+/// Like [image::imageops::resize], with the same result, but computing both passes row by row.
+fn filter_resize<P>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Sample,
+{
+    if img.width() == 0 || img.height() == 0 {
+        return ImageBuffer::new(width, height);
+    }
+    if img.dimensions() == (width, height) {
+        return img.clone();
+    }
+    let channels = P::CHANNEL_COUNT as usize;
+    let vertical = filter_weights(img.height(), height, filter);
+    let horizontal = filter_weights(img.width(), width, filter);
+
+    // resize vertically first, then horizontally, in floating point
+    let src = img.as_raw();
+    let src_len = img.width() as usize * channels;
+    let mut tmp = vec![0.0; src_len * height as usize];
+    for_each_row(&mut tmp, src_len, |y, row| {
+        let (start, weights) = &vertical[y];
+        for (i, w) in weights.iter().enumerate() {
+            let src_row = &src[(*start as usize + i) * src_len..][..src_len];
+            for (t, s) in row.iter_mut().zip(src_row) {
+                *t += s.into_f32() * w;
+            }
+        }
+    });
+
+    let mut out = ImageBuffer::new(width, height);
+    for_each_row(&mut out, width as usize * channels, |y, row| {
+        let tmp_row = &tmp[y * src_len..][..src_len];
+        for (pixel, (start, weights)) in row.chunks_exact_mut(channels).zip(&horizontal) {
+            for (c, subpixel) in pixel.iter_mut().enumerate() {
+                let mut t = 0.0;
+                for (i, w) in weights.iter().enumerate() {
+                    t += tmp_row[(*start as usize + i) * channels + c] * w;
+                }
+                *subpixel = Sample::from_f32(t);
+            }
+        }
+    });
+    out
+}
+
+/// For every target pixel, the first source pixel the filter covers and the weights of it and
+/// the following source pixels, normalized to sum up to 1.
+fn filter_weights(src: u32, dst: u32, filter: FilterType) -> Vec<(u32, Vec<f32>)> {
+    let (kernel, support) = kernel(filter);
+    let ratio = src as f32 / dst as f32;
+    // when upscaling, the filter covers the same number of source pixels as with a ratio of 1
+    let scale = if ratio < 1.0 { 1.0 } else { ratio };
+    let src_support = support * scale;
+
+    (0..dst)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio;
+            let first = ((center - src_support).floor() as i64).clamp(0, src as i64 - 1);
+            let last = ((center + src_support).ceil() as i64).clamp(first + 1, src as i64);
+
+            // the kernel is centered on the pixel, not on its left edge
+            let center = center - 0.5;
+            let mut weights: Vec<f32> = (first..last)
+                .map(|p| kernel((p as f32 - center) / scale))
+                .collect();
+            let mut total = 0.0;
+            for w in &weights {
+                total += w;
+            }
+            weights.iter_mut().for_each(|w| *w /= total);
+            (first as u32, weights)
+        })
+        .collect()
+}
+
+/// The kernel of the filter and how far it reaches, the same as in [image::imageops::resize].
+fn kernel(filter: FilterType) -> (fn(f32) -> f32, f32) {
+    match filter {
+        FilterType::Nearest => (|_| 1.0, 0.0),
+        FilterType::Triangle => (|x| if x.abs() < 1.0 { 1.0 - x.abs() } else { 0.0 }, 1.0),
+        FilterType::CatmullRom => (catmull_rom, 2.0),
+        FilterType::Gaussian => (gaussian, 3.0),
+        FilterType::Lanczos3 => (lanczos3, 3.0),
+    }
+}
+
+// Cubic spline of Mitchell and Netravali with B = 0 and C = 0.5
+fn catmull_rom(x: f32) -> f32 {
+    let a = x.abs();
+    let k = if a < 1.0 {
+        9.0 * a.powi(3) + -15.0 * a.powi(2) + 6.0
+    } else if a < 2.0 {
+        -3.0 * a.powi(3) + 15.0 * a.powi(2) + -24.0 * a + 12.0
+    } else {
+        0.0
+    };
+    k / 6.0
+}
+
+// Gaussian with a standard deviation of 0.5
+fn gaussian(x: f32) -> f32 {
+    let r: f32 = 0.5;
+    ((2.0 * std::f32::consts::PI).sqrt() * r).recip() * (-x.powi(2) / (2.0 * r.powi(2))).exp()
+}
+
+fn lanczos3(x: f32) -> f32 {
+    if x.abs() < 3.0 {
+        sinc(x) * sinc(x / 3.0)
+    } else {
+        0.0
+    }
+}
+
+fn sinc(t: f32) -> f32 {
+    if t == 0.0 {
+        1.0
+    } else {
+        let a = t * std::f32::consts::PI;
+        a.sin() / a
+    }
+}
+
+/// Average the source pixels covered by each target pixel, weighted by the covered area.
+fn box_resize(img: &Rgba32FImage, width: u32, height: u32) -> Rgba32FImage {
+    let horizontal = box_weights(img.width(), width);
+    let vertical = box_weights(img.height(), height);
+
+    // resize horizontally first, then vertically
+    let tmp: Rgba32FImage = image_from_fn(width, img.height(), |x, y| {
+        let (start, weights) = &horizontal[x as usize];
+        weighted_sum(weights, |i| img.get_pixel(start + i, y))
+    });
+    image_from_fn(width, height, |x, y| {
+        let (start, weights) = &vertical[y as usize];
+        weighted_sum(weights, |i| tmp.get_pixel(x, start + i))
+    })
+}
+
+/// For every target pixel, the first source pixel it covers and how much of each following
+/// source pixel is covered, normalized to sum up to 1.
+fn box_weights(src: u32, dst: u32) -> Vec<(u32, Vec<f32>)> {
+    let scale = src as f64 / dst as f64;
+    (0..dst)
+        .map(|i| {
+            let begin = i as f64 * scale;
+            let end = ((i + 1) as f64 * scale).min(src as f64);
+            let first = begin.floor() as u32;
+            let last = std::cmp::min(end.ceil() as u32, src).max(first + 1);
+
+            let mut weights: Vec<f32> = (first..last)
+                .map(|p| {
+                    let covered = (end.min(p as f64 + 1.0) - begin.max(p as f64)).max(0.0);
+                    covered as f32
+                })
+                .collect();
+            let total: f32 = weights.iter().sum();
+            if total > 0.0 {
+                weights.iter_mut().for_each(|w| *w /= total);
+            } else {
+                // upscaling with a target pixel inside of a single source pixel
+                weights.iter_mut().for_each(|w| *w = 1.0);
+            }
+            (first, weights)
+        })
+        .collect()
+}
+
+fn weighted_sum<'a>(weights: &[f32], pixel: impl Fn(u32) -> &'a Rgba<f32>) -> Rgba<f32> {
+    let mut sum = [0.0; 4];
+    for (i, w) in weights.iter().enumerate() {
+        let p = pixel(i as u32);
+        for c in 0..4 {
+            sum[c] += p[c] * w;
+        }
+    }
+    Rgba(sum)
+}
+
+// Lookup table from 8 bit sRGB values to linear light
+static SRGB_TO_LINEAR: LazyLock<[f32; 256]> = LazyLock::new(|| {
+    let mut table = [0.0; 256];
+    for (i, v) in table.iter_mut().enumerate() {
+        let c = i as f32 / 255.0;
+        *v = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+    }
+    table
+});
+
+fn linear_to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let c = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+/// Convert to floating point in linear light. Alpha is kept as it is.
+fn to_linear(img: &DynamicImage) -> Rgba32FImage {
+    let rgba = rgba8(img);
+    image_from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y);
+        Rgba([
+            SRGB_TO_LINEAR[p[0] as usize],
+            SRGB_TO_LINEAR[p[1] as usize],
+            SRGB_TO_LINEAR[p[2] as usize],
+            p[3] as f32 / 255.0,
+        ])
+    })
+}
+
+fn from_linear(img: &Rgba32FImage) -> DynamicImage {
+    let rgba: RgbaImage = image_from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        Rgba([
+            linear_to_srgb(p[0]),
+            linear_to_srgb(p[1]),
+            linear_to_srgb(p[2]),
+            (p[3].clamp(0.0, 1.0) * 255.0).round() as u8,
+        ])
+    });
+    DynamicImage::ImageRgba8(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Alternating black and white columns
+    fn stripes() -> DynamicImage {
+        let img = RgbaImage::from_fn(8, 2, |x, _| {
+            let v = if x % 2 == 0 { 0 } else { 255 };
+            Rgba([v, v, v, 255])
+        });
+        DynamicImage::ImageRgba8(img)
+    }
+
+    #[test]
+    fn test_box_resize_averages() {
+        let resized = resize(&stripes(), 4, 1, Resampling::Box, false).to_rgba8();
+        assert_eq!(resized.dimensions(), (4, 1));
+        for p in resized.pixels() {
+            assert_eq!(p, &Rgba([128, 128, 128, 255]));
+        }
+
+        // fractional coverage: the middle pixel is a third of each neighbour
+        let img = RgbaImage::from_fn(3, 1, |x, _| Rgba([[0, 90, 180][x as usize], 0, 0, 255]));
+        let resized = resize(&DynamicImage::ImageRgba8(img), 2, 1, Resampling::Box, false);
+        let resized = resized.to_rgba8();
+        assert_eq!(resized.get_pixel(0, 0)[0], 30);
+        assert_eq!(resized.get_pixel(1, 0)[0], 150);
+    }
+
+    #[test]
+    fn test_box_resize_upscale() {
+        let img = RgbaImage::from_fn(2, 1, |x, _| Rgba([x as u8 * 200, 0, 0, 255]));
+        let resized = resize(&DynamicImage::ImageRgba8(img), 4, 2, Resampling::Box, false);
+        let resized = resized.to_rgba8();
+        assert_eq!(resized.get_pixel(1, 1)[0], 0);
+        assert_eq!(resized.get_pixel(2, 0)[0], 200);
+    }
+
+    #[test]
+    fn test_linear_light_is_brighter() {
+        // averaging black and white is 50% light, which is 188 in sRGB instead of 128
+        let resized = resize(&stripes(), 4, 1, Resampling::Box, true).to_rgba8();
+        assert_eq!(resized.get_pixel(0, 0), &Rgba([188, 188, 188, 255]));
+
+        let filter = Resampling::Filter(FilterType::Triangle);
+        let srgb = resize(&stripes(), 4, 1, filter, false).to_rgba8();
+        let linear = resize(&stripes(), 4, 1, filter, true).to_rgba8();
+        assert!(linear.get_pixel(1, 0)[0] > srgb.get_pixel(1, 0)[0]);
+    }
+
+    #[test]
+    fn test_filter_resize_matches_image() {
+        let filters = [
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::CatmullRom,
+            FilterType::Gaussian,
+            FilterType::Lanczos3,
+        ];
+        let rgba = RgbaImage::from_fn(37, 23, |x, y| {
+            Rgba([
+                (x * 7) as u8,
+                (y * 11) as u8,
+                (x * y) as u8,
+                (x + y * 5) as u8,
+            ])
+        });
+        let images = [
+            DynamicImage::ImageRgba8(rgba.clone()),
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba.clone()).to_rgb8()),
+            DynamicImage::ImageLumaA8(DynamicImage::ImageRgba8(rgba).to_luma_alpha8()),
+        ];
+        for img in &images {
+            for filter in filters {
+                for (width, height) in [(10, 6), (37, 50), (80, 9), (1, 1)] {
+                    let expected = img.resize_exact(width, height, filter);
+                    assert_eq!(resize_exact(img, width, height, filter), expected);
+                }
+            }
+        }
+
+        let linear = to_linear(&images[0]);
+        let expected = image::imageops::resize(&linear, 12, 40, FilterType::CatmullRom);
+        assert_eq!(
+            filter_resize(&linear, 12, 40, FilterType::CatmullRom),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_srgb_roundtrip() {
+        for i in 0..=255u8 {
+            assert_eq!(linear_to_srgb(SRGB_TO_LINEAR[i as usize]), i);
+        }
+    }
+}
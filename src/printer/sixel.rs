@@ -1,7 +1,10 @@
 use crate::error::ViuResult;
-use crate::printer::{adjust_offset, find_best_fit, Printer, ReadKey};
+use crate::printer::backdrop::{flatten, half_cell};
+use crate::printer::layout::Layout;
+use crate::printer::parallel::rgba8;
+use crate::printer::{adjust_offset, Encoded, Printer, ReadKey, Source};
 use crate::Config;
-use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use image::imageops::FilterType;
 use sixel_rs::encoder::{Encoder, QuickFrameBuilder};
 use sixel_rs::optflags::EncodePolicy;
 use std::io::Write;
@@ -10,38 +13,68 @@ use std::io::Write;
 pub struct SixelPrinter;
 
 impl Printer for SixelPrinter {
-    fn print(
+    fn print_source(
         &self,
         _stdin: &impl ReadKey,
         stdout: &mut impl Write,
-        img: &DynamicImage,
+        source: &Source,
         config: &Config,
     ) -> ViuResult<(u32, u32)> {
-        let (w, h) = find_best_fit(img, config.width, config.height);
-
-        //TODO: the max 1000 width is an xterm bug workaround, other terminals may not be affected
-        let resized_img =
-            img.resize_exact(std::cmp::min(6 * w, 1000), 12 * h, FilterType::Triangle);
+        write_encoded(stdout, &encode(source, config)?, config)
+    }
+}
 
-        let (width, height) = resized_img.dimensions();
+// Size of the image in pixels, for the given layout
+fn sixel_size(layout: &Layout) -> (u32, u32) {
+    //TODO: the max 1000 width is an xterm bug workaround, other terminals may not be affected
+    (std::cmp::min(6 * layout.width, 1000), 12 * layout.height)
+}
 
-        let rgba = resized_img.to_rgba8();
-        let raw = rgba.as_raw();
+/// Encode the image for [write_encoded]. The encoder only writes to stdout or to a file, so
+/// the sixel data is read back from a temporary file.
+pub(crate) fn encode(source: &Source, config: &Config) -> ViuResult<Encoded> {
+    let layout = source.layout;
+    let (width, height) = sixel_size(&layout);
+    let resized_img = layout.resize(&source.img, width, height, config, FilterType::Triangle);
 
-        adjust_offset(stdout, config)?;
+    // sixel has no notion of partial transparency, so blend with the backdrop here
+    let rgba = flatten(&rgba8(&resized_img), config, half_cell());
 
+    let file = tempfile::NamedTempFile::new()?;
+    {
+        // the output file is closed when the encoder is dropped
         let encoder = Encoder::new()?;
-
+        encoder.set_output(file.path())?;
         encoder.set_encode_policy(EncodePolicy::Fast)?;
 
         let frame = QuickFrameBuilder::new()
             .width(width as usize)
             .height(height as usize)
             .format(sixel_rs::sys::PixelFormat::RGBA8888)
-            .pixels(raw.to_vec());
-
+            .pixels(rgba.into_raw());
         encoder.encode_bytes(frame)?;
+    }
+
+    Ok(Encoded {
+        layout,
+        data: vec![std::fs::read(file.path())?],
+    })
+}
+
+/// Print an image encoded by [encode].
+pub(crate) fn write_encoded(
+    stdout: &mut impl Write,
+    encoded: &Encoded,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let layout = &encoded.layout;
+    let placed = layout.start(stdout, config)?;
+    adjust_offset(stdout, &placed)?;
 
-        Ok((w, h))
+    for data in &encoded.data {
+        stdout.write_all(data)?;
     }
+    layout.finish(stdout)?;
+    stdout.flush()?;
+    Ok((layout.box_width, layout.box_height))
 }
@@ -0,0 +1,100 @@
+use crate::config::Config;
+use crate::error::ViuResult;
+use crate::printer::layout::Layout;
+use crate::printer::orientation::{is_oriented, orient};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel};
+use std::borrow::Cow;
+
+/// An image as it is printed: rotated and flipped as configured, along with its layout.
+#[derive(Debug, Clone)]
+pub(crate) struct Source<'a> {
+    pub img: Cow<'a, DynamicImage>,
+    pub layout: Layout,
+}
+
+impl<'a> Source<'a> {
+    pub(crate) fn new(img: &'a DynamicImage, config: &Config) -> ViuResult<Self> {
+        let img = orient(img, config);
+        let layout = Layout::new(&img, config)?;
+        Ok(Self { img, layout })
+    }
+}
+
+impl Source<'static> {
+    /// Copy the pixels of any image, keeping their format. Unless the image is rotated or
+    /// flipped, only the part that is printed is copied, and the layout is adjusted to it.
+    pub(crate) fn from_view<V, P>(view: &V, config: &Config) -> ViuResult<Self>
+    where
+        V: GenericImageView<Pixel = P>,
+        P: Pixel<Subpixel = u8>,
+        DynamicImage: From<ImageBuffer<P, Vec<u8>>>,
+    {
+        let (width, height) = view.dimensions();
+        if is_oriented(config) {
+            // rotating and flipping needs the whole image
+            let img = DynamicImage::from(ImageBuffer::from_fn(width, height, |x, y| {
+                view.get_pixel(x, y)
+            }));
+            let img = orient(&img, config).into_owned();
+            let layout = Layout::new(&img, config)?;
+            return Ok(Self {
+                img: Cow::Owned(img),
+                layout,
+            });
+        }
+
+        let mut layout = Layout::with_size(width, height, config)?;
+        let (x, y, w, h) = layout.crop;
+        let visible = ImageBuffer::from_fn(w, h, |px, py| view.get_pixel(x + px, y + py));
+        layout.crop = (0, 0, w, h);
+        Ok(Self {
+            img: Cow::Owned(visible.into()),
+            layout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Rect, Rotation};
+    use image::{Rgb, RgbImage};
+
+    fn image() -> RgbImage {
+        RgbImage::from_fn(8, 6, |x, y| Rgb([x as u8, y as u8, 0]))
+    }
+
+    #[test]
+    fn test_source_from_view_copies_visible_part() {
+        let config = Config {
+            source_rect: Some(Rect::new(2, 1, 4, 4)),
+            ..Default::default()
+        };
+        let source = Source::from_view(&image(), &config).unwrap();
+        let img = DynamicImage::ImageRgb8(image());
+        let expected = Source::new(&img, &config).unwrap();
+
+        assert_eq!(source.layout.crop, (0, 0, 4, 4));
+        assert_eq!(
+            (source.layout.width, source.layout.height),
+            (expected.layout.width, expected.layout.height)
+        );
+        assert_eq!(
+            *source.img,
+            expected.layout.crop(&expected.img).into_owned()
+        );
+    }
+
+    #[test]
+    fn test_source_from_view_oriented() {
+        let config = Config {
+            rotate: Rotation::Deg90,
+            ..Default::default()
+        };
+        let source = Source::from_view(&image(), &config).unwrap();
+        let img = DynamicImage::ImageRgb8(image());
+        let expected = Source::new(&img, &config).unwrap();
+        assert_eq!(source.img, expected.img);
+        assert_eq!(source.layout, expected.layout);
+    }
+}
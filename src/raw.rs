@@ -0,0 +1,144 @@
+use crate::config::Config;
+use crate::error::{ViuError, ViuResult};
+use image::flat::{FlatSamples, SampleLayout, View};
+use image::{DynamicImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, Rgba};
+
+/// Format of the pixels in a raw buffer, with 8 bits per channel. See [print_raw].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Grayscale.
+    Luma8,
+    /// Grayscale with alpha.
+    LumaA8,
+    /// Red, green and blue.
+    Rgb8,
+    /// Red, green and blue with alpha.
+    Rgba8,
+}
+
+impl PixelFormat {
+    /// Number of bytes per pixel.
+    pub fn channels(self) -> u8 {
+        match self {
+            PixelFormat::Luma8 => 1,
+            PixelFormat::LumaA8 => 2,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 => 4,
+        }
+    }
+}
+
+/// Print pixels from a raw buffer, like a camera frame or a numeric array, without copying
+/// them into a [DynamicImage] first. Rows of `width` pixels start
+/// `stride` bytes apart, which may be more than a row takes up. Otherwise, works like
+/// [print](crate::print), see [print_view](crate::print_view).
+///
+/// Returns [ViuError::InvalidConfiguration] if the buffer is too short for the dimensions or
+/// the stride is shorter than a row.
+///
+/// ## Example
+/// ```no_run
+/// use viuer::{print_raw, Config, PixelFormat};
+///
+/// // 60x40 grayscale pixels, with rows padded to 64 bytes
+/// let pixels = vec![128; 64 * 40];
+/// print_raw(&pixels, 60, 40, 64, PixelFormat::Luma8, &Config::default())
+///     .expect("Image printing failed.");
+/// ```
+pub fn print_raw(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let samples = samples(pixels, width, height, stride, format.channels())?;
+    match format {
+        PixelFormat::Luma8 => print_samples::<Luma<u8>>(&samples, config),
+        PixelFormat::LumaA8 => print_samples::<LumaA<u8>>(&samples, config),
+        PixelFormat::Rgb8 => print_samples::<Rgb<u8>>(&samples, config),
+        PixelFormat::Rgba8 => print_samples::<Rgba<u8>>(&samples, config),
+    }
+}
+
+/// Describe the layout of the buffer, checking that rows do not overlap.
+fn samples(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    channels: u8,
+) -> ViuResult<FlatSamples<&[u8]>> {
+    if stride < width as usize * channels as usize {
+        return Err(ViuError::InvalidConfiguration(
+            "the stride is shorter than a row of pixels".to_owned(),
+        ));
+    }
+
+    Ok(FlatSamples {
+        samples: pixels,
+        layout: SampleLayout {
+            channels,
+            channel_stride: 1,
+            width,
+            width_stride: channels as usize,
+            height,
+            height_stride: stride,
+        },
+        color_hint: None,
+    })
+}
+
+/// View the buffer as an image, checking that it holds all of the pixels.
+fn view<'a, P: Pixel<Subpixel = u8>>(
+    samples: &'a FlatSamples<&[u8]>,
+) -> ViuResult<View<&'a [u8], P>> {
+    samples
+        .as_view()
+        .map_err(|err| ViuError::InvalidConfiguration(format!("invalid pixel buffer: {err:?}")))
+}
+
+fn print_samples<P>(samples: &FlatSamples<&[u8]>, config: &Config) -> ViuResult<(u32, u32)>
+where
+    P: Pixel<Subpixel = u8>,
+    DynamicImage: From<ImageBuffer<P, Vec<u8>>>,
+{
+    crate::print_view(&view::<P>(samples)?, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    #[test]
+    fn test_view_with_stride() {
+        // 2x2 RGB pixels, with two bytes of padding after each row
+        let pixels = [1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12, 0, 0];
+        let samples = samples(&pixels, 2, 2, 8, 3).unwrap();
+        let img = view::<Rgb<u8>>(&samples).unwrap();
+        assert_eq!(img.dimensions(), (2, 2));
+        assert_eq!(img.get_pixel(1, 0), Rgb([4, 5, 6]));
+        assert_eq!(img.get_pixel(0, 1), Rgb([7, 8, 9]));
+
+        // the padding of the last row may be left out
+        let samples = super::samples(&pixels[..14], 2, 2, 8, 3).unwrap();
+        assert!(view::<Rgb<u8>>(&samples).is_ok());
+    }
+
+    #[test]
+    fn test_view_invalid() {
+        let pixels = [0; 16];
+        assert!(matches!(
+            samples(&pixels, 2, 2, 4, 4),
+            Err(ViuError::InvalidConfiguration(_))
+        ));
+        let short = samples(&pixels, 2, 3, 8, 4).unwrap();
+        assert!(matches!(
+            view::<Rgba<u8>>(&short),
+            Err(ViuError::InvalidConfiguration(_))
+        ));
+        assert_eq!(PixelFormat::LumaA8.channels(), LumaA::<u8>::CHANNEL_COUNT);
+    }
+}
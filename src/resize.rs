@@ -0,0 +1,242 @@
+use crate::config::Config;
+use crate::error::ViuResult;
+use crate::grid::{print_tiles, Grid};
+use crate::printer::{Printer, ReadKey};
+use crate::utils::terminal_size;
+use console::Term;
+use crossterm::{cursor::MoveTo, execute};
+use image::DynamicImage;
+use std::io::Write;
+use std::time::Duration;
+
+/// What a [ResizeWatcher] redraws.
+#[derive(Debug, Clone)]
+enum Content {
+    Image(DynamicImage),
+    Grid(Vec<DynamicImage>, Grid),
+}
+
+/// Redraws an image or a grid of images whenever the terminal is resized, so that sizes
+/// derived from the terminal size stay correct.
+///
+/// Resize events are passed in by the caller: either the sizes reported by
+/// [resize_events], or those of an existing event loop, such as crossterm's
+/// `Event::Resize`. On every size change, the screen is cleared and the content is printed
+/// again from the top left corner of the terminal, offset by `x` and `y`.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, RgbaImage};
+/// use std::time::Duration;
+/// use viuer::{resize_events, Config, ResizeWatcher};
+///
+/// let img = DynamicImage::ImageRgba8(RgbaImage::new(40, 40));
+/// let mut watcher = ResizeWatcher::image(img, Config::default());
+/// watcher.draw().expect("Image printing failed.");
+/// // redraw until the process is stopped
+/// watcher
+///     .watch(resize_events(Duration::from_millis(100)))
+///     .expect("Image printing failed.");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResizeWatcher {
+    content: Content,
+    config: Config,
+    // terminal size at the last draw
+    size: (u16, u16),
+}
+
+impl ResizeWatcher {
+    /// Watch a single image, printed like [print](crate::print) does.
+    pub fn image(img: DynamicImage, config: Config) -> Self {
+        Self::new(Content::Image(img), config)
+    }
+
+    /// Watch several images, printed like [print_grid](crate::print_grid) does.
+    pub fn grid(images: Vec<DynamicImage>, grid: Grid, config: Config) -> Self {
+        Self::new(Content::Grid(images, grid), config)
+    }
+
+    fn new(content: Content, config: Config) -> Self {
+        Self {
+            content,
+            config,
+            size: terminal_size(),
+        }
+    }
+
+    /// Clear the screen and print the content for the current terminal size.
+    pub fn draw(&mut self) -> ViuResult {
+        // This is required to get a "Term" instance for "::read_key"
+        let term = Term::stdout();
+        let printer = crate::choose_printer(&self.config);
+        self.draw_with(&printer, &term, &mut std::io::stdout(), terminal_size())
+    }
+
+    /// Handle the terminal being resized to `columns` by `rows` cells. The content is only
+    /// drawn again if the size differs from the last draw. Returns whether it was.
+    pub fn resize(&mut self, columns: u16, rows: u16) -> ViuResult<bool> {
+        let term = Term::stdout();
+        let printer = crate::choose_printer(&self.config);
+        self.resize_with(&printer, &term, &mut std::io::stdout(), (columns, rows))
+    }
+
+    /// Handle every size yielded by `events` with [ResizeWatcher::resize], until there are
+    /// no more.
+    pub fn watch<I: IntoIterator<Item = (u16, u16)>>(&mut self, events: I) -> ViuResult {
+        let term = Term::stdout();
+        let printer = crate::choose_printer(&self.config);
+        let mut stdout = std::io::stdout();
+        for size in events {
+            self.resize_with(&printer, &term, &mut stdout, size)?;
+        }
+        Ok(())
+    }
+
+    fn resize_with(
+        &mut self,
+        printer: &impl Printer,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        size: (u16, u16),
+    ) -> ViuResult<bool> {
+        if size == self.size {
+            return Ok(false);
+        }
+        self.draw_with(printer, stdin, stdout, size)?;
+        Ok(true)
+    }
+
+    fn draw_with(
+        &mut self,
+        printer: &impl Printer,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+        size: (u16, u16),
+    ) -> ViuResult {
+        self.size = size;
+        printer.clear(stdout)?;
+        execute!(stdout, MoveTo(0, 0))?;
+
+        // lay the content out for the new size, which the terminal may not report yet
+        let config = Config {
+            restore_cursor: false,
+            terminal_size: Some(size),
+            ..self.config.clone()
+        };
+        match &self.content {
+            Content::Image(img) => {
+                printer.print(stdin, stdout, img, &config)?;
+            }
+            Content::Grid(images, grid) => {
+                print_tiles(printer, stdin, stdout, images, grid, &config)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Yield the terminal size whenever it changes, see [ResizeWatcher::watch]. With the `events`
+/// feature on Unix, this waits for crossterm's `Event::Resize`, which is sent on SIGWINCH.
+/// Other events read from the terminal in the meantime are dropped. Otherwise, the size is
+/// polled every `interval`. The iterator ends only if events cannot be read.
+pub fn resize_events(interval: Duration) -> impl Iterator<Item = (u16, u16)> {
+    #[cfg(all(unix, feature = "events"))]
+    {
+        // the signal makes polling unnecessary
+        let _ = interval;
+        resize_signals()
+    }
+    #[cfg(not(all(unix, feature = "events")))]
+    poll_size(interval)
+}
+
+#[cfg(all(unix, feature = "events"))]
+fn resize_signals() -> impl Iterator<Item = (u16, u16)> {
+    use crossterm::event::{read, Event};
+    std::iter::from_fn(|| loop {
+        match read() {
+            Ok(Event::Resize(columns, rows)) => return Some((columns, rows)),
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+    })
+}
+
+#[cfg(not(all(unix, feature = "events")))]
+fn poll_size(interval: Duration) -> impl Iterator<Item = (u16, u16)> {
+    let mut last = terminal_size();
+    std::iter::from_fn(move || loop {
+        std::thread::sleep(interval);
+        let size = terminal_size();
+        if size != last {
+            last = size;
+            return Some(size);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::iTermPrinter;
+    use crate::printer::read_key::test_utils::TestKeys;
+
+    fn image() -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4))
+    }
+
+    fn resize_all(watcher: &mut ResizeWatcher, sizes: &[(u16, u16)]) -> (String, Vec<bool>) {
+        let mut vec = Vec::new();
+        let redrawn = sizes
+            .iter()
+            .map(|&size| {
+                watcher
+                    .resize_with(&iTermPrinter, &TestKeys::new(&[]), &mut vec, size)
+                    .unwrap()
+            })
+            .collect();
+        (String::from_utf8(vec).unwrap(), redrawn)
+    }
+
+    #[test]
+    fn test_resize_redraws_on_change() {
+        let mut watcher = ResizeWatcher::image(image(), Config::default());
+        let (result, redrawn) = resize_all(&mut watcher, &[(80, 24), (100, 30), (100, 30)]);
+
+        assert_eq!(redrawn, vec![false, true, false]);
+        assert_eq!(result.matches("\x1b[2J\x1b[1;1H").count(), 1);
+        assert_eq!(result.matches("\x1b]1337;File=inline=1").count(), 1);
+        assert_eq!(watcher.size, (100, 30));
+    }
+
+    #[test]
+    fn test_resize_grid() {
+        let grid = Grid {
+            columns: Some(2),
+            ..Default::default()
+        };
+        let config = Config {
+            absolute_offset: false,
+            ..Default::default()
+        };
+        let mut watcher = ResizeWatcher::grid(vec![image(); 3], grid, config);
+        let (result, redrawn) = resize_all(&mut watcher, &[(60, 20), (80, 24)]);
+
+        assert_eq!(redrawn, vec![true, true]);
+        assert_eq!(result.matches("\x1b[2J").count(), 2);
+        assert_eq!(result.matches("\x1b]1337;File=inline=1").count(), 6);
+    }
+
+    #[test]
+    fn test_resize_uses_new_size() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(100, 100));
+        let mut watcher = ResizeWatcher::image(img, Config::default());
+
+        // the image is fit into the new size, less the line for the prompt
+        let (result, _) = resize_all(&mut watcher, &[(40, 11)]);
+        assert!(result.contains(";width=22;height=10:"), "{result}");
+        let (result, _) = resize_all(&mut watcher, &[(100, 41)]);
+        assert!(result.contains(";width=82;height=40:"), "{result}");
+    }
+}
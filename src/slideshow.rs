@@ -0,0 +1,352 @@
+use crate::config::{Config, Rect, Rotation};
+use crate::error::ViuResult;
+use crate::grid::Grid;
+use crate::printer::orientation;
+use crate::printer::{Printer, ReadKey};
+use console::Term;
+use crossterm::{cursor::MoveTo, execute};
+use image::{DynamicImage, ImageReader};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// List the image files in a directory, or those matching a pattern like `photos/*.jpg`, sorted
+/// by name. Patterns may contain `*` and `?` in the file name only. A path to a single file is
+/// returned as is, if it is an image.
+///
+/// Files are recognized by their content, see [ImageReader::with_guessed_format]. Those that
+/// cannot be read are left out. The feature is available only with the `print-file` feature.
+pub fn image_files<P: AsRef<Path>>(path: P) -> ViuResult<Vec<PathBuf>> {
+    let path = path.as_ref();
+    let mut files = if path.is_dir() {
+        entries(path, |_| true)?
+    } else {
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(pattern) if pattern.contains(['*', '?']) => {
+                let dir = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                let pattern: Vec<char> = pattern.chars().collect();
+                entries(dir, |name| {
+                    let name: Vec<char> = name.chars().collect();
+                    matches(&pattern, &name)
+                })?
+            }
+            _ => vec![path.to_path_buf()],
+        }
+    };
+
+    files.retain(|file| is_image(file));
+    files.sort();
+    Ok(files)
+}
+
+/// Files in `dir` whose name is accepted by `filter`.
+fn entries(dir: &Path, filter: impl Fn(&str) -> bool) -> ViuResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let accepted = path.file_name().and_then(|name| name.to_str()).map(&filter);
+        if path.is_file() && accepted == Some(true) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Match a file name against a pattern with `*` and `?` wildcards.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the rest of the name after it. Only the
+    // last one needs to be retried, with one more character, when the rest does not match.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn is_image(path: &Path) -> bool {
+    ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .is_ok_and(|reader| reader.format().is_some())
+}
+
+/// Decode the files one after another on a background thread, skipping those that fail.
+/// Only one image is decoded ahead of the one being consumed.
+fn prefetch(files: Vec<PathBuf>, config: &Config) -> impl Iterator<Item = (PathBuf, DynamicImage)> {
+    let (sender, receiver) = mpsc::sync_channel(0);
+    let config = config.clone();
+    std::thread::spawn(move || {
+        for file in files {
+            let Ok(reader) = ImageReader::open(&file) else {
+                continue;
+            };
+            if let Ok((img, _)) = orientation::decode(reader, &config) {
+                // stop once the receiver is gone
+                if sender.send((file, img)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver.into_iter()
+}
+
+/// Show the images from [image_files] one after another, each for `interval`. The screen is
+/// cleared before every image, which is then printed according to the config. The next image
+/// is decoded in the background while the current one is shown, and files that cannot be
+/// decoded are skipped.
+///
+/// Returns the number of images shown. The feature is available only with the `print-file`
+/// feature.
+///
+/// ## Example
+/// ```no_run
+/// use std::time::Duration;
+/// use viuer::{slideshow, Config};
+///
+/// let shown = slideshow("photos/*.jpg", Duration::from_secs(3), &Config::default())
+///     .expect("Slideshow failed.");
+/// println!("{shown} images");
+/// ```
+pub fn slideshow<P: AsRef<Path>>(path: P, interval: Duration, config: &Config) -> ViuResult<usize> {
+    let files = image_files(path)?;
+    // This is required to get a "Term" instance for "::read_key"
+    let term = Term::stdout();
+    let printer = crate::choose_printer(config);
+    show_slides(
+        &printer,
+        &term,
+        &mut std::io::stdout(),
+        prefetch(files, config),
+        interval,
+        config,
+    )
+}
+
+fn show_slides(
+    printer: &impl Printer,
+    stdin: &impl ReadKey,
+    stdout: &mut impl Write,
+    images: impl Iterator<Item = (PathBuf, DynamicImage)>,
+    interval: Duration,
+    config: &Config,
+) -> ViuResult<usize> {
+    let config = Config {
+        restore_cursor: false,
+        ..config.clone()
+    };
+
+    let mut shown: Option<Instant> = None;
+    let mut count = 0;
+    for (_, img) in images {
+        if let Some(shown) = shown {
+            std::thread::sleep(interval.saturating_sub(shown.elapsed()));
+        }
+        printer.clear(stdout)?;
+        execute!(stdout, MoveTo(0, 0))?;
+        printer.print(stdin, stdout, &img, &config)?;
+
+        shown = Some(Instant::now());
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Print the images from [image_files] as thumbnails in a grid, like [print_grid](crate::print_grid)
+/// does. Files that cannot be decoded are skipped. Every image is shrunk to the size of its tile
+/// right after decoding, so that only the thumbnails are kept in memory.
+///
+/// Returns the files that were printed, with the position and size of their tile. The feature
+/// is available only with the `print-file` feature.
+///
+/// ## Example
+/// ```no_run
+/// use viuer::{print_index, Config, Grid};
+///
+/// let conf = Config {
+///     absolute_offset: false,
+///     ..Default::default()
+/// };
+/// for (file, tile) in print_index("photos", &Grid::default(), &conf).expect("Printing failed.") {
+///     println!("{}: column {}, row {}", file.display(), tile.x, tile.y);
+/// }
+/// ```
+pub fn print_index<P: AsRef<Path>>(
+    path: P,
+    grid: &Grid,
+    config: &Config,
+) -> ViuResult<Vec<(PathBuf, Rect)>> {
+    // only the thumbnails are kept, not the decoded images
+    let tile = grid.tile_pixels(config);
+    let (files, images): (Vec<_>, Vec<_>) = prefetch(image_files(path)?, config)
+        .map(|(file, img)| (file, thumbnail(img, tile, config)))
+        .unzip();
+    let tiles = crate::print_grid(&images, grid, config)?;
+    Ok(files.into_iter().zip(tiles).collect())
+}
+
+/// Shrink the image so that it still covers a tile of `width`x`height` pixels once it is
+/// rotated. Images with a source rectangle are kept as they are, as it is given in their pixels.
+fn thumbnail(img: DynamicImage, (width, height): (u32, u32), config: &Config) -> DynamicImage {
+    let (width, height) = match config.rotate {
+        Rotation::Deg90 | Rotation::Deg270 => (height, width),
+        Rotation::None | Rotation::Deg180 => (width, height),
+    };
+    let scale = f64::max(
+        width as f64 / img.width() as f64,
+        height as f64 / img.height() as f64,
+    );
+    if config.source_rect.is_some() || scale >= 1.0 {
+        return img;
+    }
+    let scaled = |size: u32| std::cmp::max(1, (size as f64 * scale).ceil() as u32);
+    img.thumbnail_exact(scaled(img.width()), scaled(img.height()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::iTermPrinter;
+    use crate::printer::read_key::test_utils::TestKeys;
+
+    // a.png, c.png and d.image look like images, but c.png cannot be decoded
+    fn directory() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let img = image::RgbaImage::new(2, 2);
+        img.save(dir.path().join("a.png")).unwrap();
+        img.save_with_format(dir.path().join("d.image"), image::ImageFormat::Png)
+            .unwrap();
+        std::fs::write(dir.path().join("b.txt"), "not an image").unwrap();
+        std::fs::write(dir.path().join("c.png"), b"\x89PNG\r\n\x1a\n broken").unwrap();
+        std::fs::create_dir(dir.path().join("e.png")).unwrap();
+        dir
+    }
+
+    fn names(files: &[PathBuf]) -> Vec<&str> {
+        files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        let matches = |pattern: &str, name: &str| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let name: Vec<char> = name.chars().collect();
+            matches(&pattern, &name)
+        };
+        assert!(matches("*.png", "a.png"));
+        assert!(matches("*", ""));
+        assert!(matches("img_??.*", "img_01.jpg"));
+        assert!(!matches("img_??.*", "img_1.jpg"));
+        assert!(!matches("*.png", "a.png.txt"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("a*?", "a"));
+
+        // without backtracking into every star, this takes forever
+        let name = "a".repeat(100);
+        assert!(!matches("*a*a*a*a*a*a*a*a*a*a*b", &name));
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        use image::GenericImageView;
+
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(1000, 500));
+        let config = Config::default();
+        assert_eq!(
+            thumbnail(img.clone(), (200, 50), &config).dimensions(),
+            (200, 100)
+        );
+
+        // a rotated image needs to cover a tile of the opposite shape
+        let config = Config {
+            rotate: Rotation::Deg90,
+            ..Default::default()
+        };
+        assert_eq!(
+            thumbnail(img.clone(), (200, 50), &config).dimensions(),
+            (400, 200)
+        );
+
+        // nothing to shrink
+        let config = Config::default();
+        assert_eq!(
+            thumbnail(img.clone(), (2000, 50), &config).dimensions(),
+            (1000, 500)
+        );
+        let config = Config {
+            source_rect: Some(Rect::new(0, 0, 10, 10)),
+            ..Default::default()
+        };
+        assert_eq!(thumbnail(img, (200, 50), &config).dimensions(), (1000, 500));
+    }
+
+    #[test]
+    fn test_image_files() {
+        let dir = directory();
+        let files = image_files(dir.path()).unwrap();
+        assert_eq!(names(&files), vec!["a.png", "c.png", "d.image"]);
+
+        let files = image_files(dir.path().join("*.png")).unwrap();
+        assert_eq!(names(&files), vec!["a.png", "c.png"]);
+
+        let files = image_files(dir.path().join("b.txt")).unwrap();
+        assert!(files.is_empty());
+        assert!(image_files(dir.path().join("missing/*.png")).is_err());
+    }
+
+    #[test]
+    fn test_slideshow_skips_broken_files() {
+        let dir = directory();
+        let config = Config::default();
+        let images = prefetch(image_files(dir.path()).unwrap(), &config);
+
+        let mut vec = Vec::new();
+        let count = show_slides(
+            &iTermPrinter,
+            &TestKeys::new(&[]),
+            &mut vec,
+            images,
+            Duration::ZERO,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+
+        let result = std::str::from_utf8(&vec).unwrap();
+        assert_eq!(result.matches("\x1b[2J\x1b[1;1H").count(), 2);
+        assert_eq!(result.matches("\x1b]1337;File=inline=1").count(), 2);
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let dir = directory();
+        let images: Vec<_> = prefetch(image_files(dir.path()).unwrap(), &Config::default())
+            .map(|(file, _)| file)
+            .collect();
+        assert_eq!(names(&images), vec!["a.png", "d.image"]);
+    }
+}
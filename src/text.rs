@@ -0,0 +1,263 @@
+use crate::config::Config;
+use crate::error::ViuResult;
+use crate::printer::{cursor_position, Printer, ReadKey, Source};
+use console::{measure_text_width, Term};
+use crossterm::{
+    cursor::{MoveRight, MoveTo, MoveToPreviousLine, RestorePosition, SavePosition},
+    execute,
+};
+use image::DynamicImage;
+use std::io::Write;
+
+/// Print an image with lines of text to the right of it, like `neofetch` does. The first line
+/// is printed next to the top row of the image, and so on. Lines that do not fit next to the
+/// image continue below it, at the same column.
+///
+/// `gap` is the number of empty cells between the image and the text. Lines may contain
+/// color escape sequences, which do not count towards their width.
+///
+/// Returns the size of the image and text together, in terminal cells. The cursor ends up
+/// below both, unless `restore_cursor` is set.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, RgbaImage};
+/// use viuer::{print_with_text, Config, Size};
+///
+/// let img = DynamicImage::ImageRgba8(RgbaImage::new(40, 40));
+/// let conf = Config {
+///     width: Some(Size::Cells(20)),
+///     absolute_offset: false,
+///     ..Default::default()
+/// };
+/// let lines = ["user@host", "---------", "OS: Linux"];
+/// print_with_text(&img, &lines, 3, &conf).expect("Image printing failed.");
+/// ```
+pub fn print_with_text<S: AsRef<str>>(
+    img: &DynamicImage,
+    lines: &[S],
+    gap: u16,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let mut stdout = std::io::stdout();
+    if config.restore_cursor {
+        execute!(&mut stdout, SavePosition)?;
+    }
+
+    // This is required to get a "Term" instance for "::read_key"
+    let term = Term::stdout();
+
+    let printer = crate::choose_printer(config);
+    let size = print_beside(&printer, &term, &mut stdout, img, lines, gap, config)?;
+
+    if config.restore_cursor {
+        execute!(&mut stdout, RestorePosition)?;
+    };
+
+    Ok(size)
+}
+
+fn print_beside<S: AsRef<str>>(
+    printer: &impl Printer,
+    stdin: &impl ReadKey,
+    stdout: &mut impl Write,
+    img: &DynamicImage,
+    lines: &[S],
+    gap: u16,
+    config: &Config,
+) -> ViuResult<(u32, u32)> {
+    let image_config = Config {
+        restore_cursor: false,
+        ..config.clone()
+    };
+    let source = Source::new(img, &image_config)?;
+    let (w, h) = printer.print_source(stdin, stdout, &source, &image_config)?;
+
+    if lines.is_empty() {
+        return Ok((w, h));
+    }
+    // the text starts next to the top row of the image, right of its box
+    let layout = source.layout;
+    let right = layout.box_x.saturating_add(layout.box_width);
+    let column = config
+        .x
+        .saturating_add(u16::try_from(right).unwrap_or(u16::MAX))
+        .saturating_add(gap);
+    // rows from the top of the image to the bottom of the box
+    let image_rows = layout.box_height - layout.offset_y;
+
+    let mut text_width = 0;
+    if config.absolute_offset {
+        // independent of where the printer leaves the cursor
+        let top = (std::cmp::max(0, config.y) as u32)
+            .saturating_add(layout.box_y)
+            .saturating_add(layout.offset_y);
+        let row =
+            |i: u32| cursor_position(u16::try_from(top.saturating_add(i)).unwrap_or(u16::MAX));
+        for (i, line) in (0..).zip(lines) {
+            let line = line.as_ref();
+            execute!(stdout, MoveTo(cursor_position(column), row(i)))?;
+            write!(stdout, "{line}")?;
+            text_width = std::cmp::max(text_width, measure_text_width(line) as u32);
+        }
+        let below = row(std::cmp::max(image_rows, lines.len() as u32));
+        execute!(stdout, MoveTo(0, below))?;
+    } else {
+        // Relative offsets leave nothing but the cursor to go by. Every printer leaves it on
+        // the line below the box, see Layout::finish.
+        let up = u16::try_from(image_rows).unwrap_or(u16::MAX);
+        execute!(stdout, MoveToPreviousLine(up))?;
+        for line in lines {
+            let line = line.as_ref();
+            // Some terminals interpret 0 as 1, see MoveRight documentation
+            if column > 0 {
+                execute!(stdout, MoveRight(column))?;
+            }
+            write!(stdout, "{line}\r\n")?;
+            text_width = std::cmp::max(text_width, measure_text_width(line) as u32);
+        }
+        // move below the image again, if the text is shorter
+        for _ in lines.len()..image_rows as usize {
+            writeln!(stdout)?;
+        }
+    }
+    stdout.flush()?;
+
+    let width = w + gap as u32 + text_width;
+    let height = std::cmp::max(h, layout.offset_y + lines.len() as u32);
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::read_key::test_utils::TestKeys;
+    use crate::printer::{iTermPrinter, AnsiBlockPrinter, KittyPrinter};
+
+    fn print_with(printer: &impl Printer, lines: &[&str], config: &Config) -> (String, (u32, u32)) {
+        // 4x2 cells
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4));
+        let mut vec = Vec::new();
+        let size = print_beside(
+            printer,
+            &TestKeys::new(&[]),
+            &mut vec,
+            &img,
+            lines,
+            2,
+            config,
+        )
+        .unwrap();
+        (String::from_utf8(vec).unwrap(), size)
+    }
+
+    fn print(lines: &[&str], config: &Config) -> (String, (u32, u32)) {
+        print_with(&iTermPrinter, lines, config)
+    }
+
+    #[test]
+    fn test_print_with_text() {
+        let config = Config {
+            x: 1,
+            ..Default::default()
+        };
+        let (result, size) = print(&["ab", "\x1b[31mcde\x1b[0m", "f"], &config);
+        assert_eq!(size, (4 + 2 + 3, 3));
+
+        let text = result.split('\x07').nth(1).unwrap();
+        assert_eq!(
+            text,
+            "\n\x1b[1;8Hab\x1b[2;8H\x1b[31mcde\x1b[0m\x1b[3;8Hf\x1b[4;1H"
+        );
+    }
+
+    #[test]
+    fn test_print_with_short_text() {
+        let config = Config {
+            absolute_offset: false,
+            ..Default::default()
+        };
+        let (result, size) = print(&["ab"], &config);
+        assert_eq!(size, (8, 2));
+        assert!(result.ends_with("\x07\n\x1b[2F\x1b[6Cab\r\n\n"));
+
+        let (result, size) = print(&[], &config);
+        assert_eq!(size, (4, 2));
+        assert!(result.ends_with("\x07\n"));
+    }
+
+    #[test]
+    fn test_print_with_text_aligned() {
+        let config = Config {
+            align: Some(crate::Align::default()),
+            align_area: Some(crate::Rect::new(0, 0, 20, 10)),
+            ..Default::default()
+        };
+        let (result, _) = print(&["ab", "c"], &config);
+
+        // the image is at column 9 and row 5, the text right of it
+        assert!(result.starts_with("\x1b[5;9H\x1b]1337;"));
+        assert!(result.ends_with("\x1b[5;15Hab\x1b[6;15Hc\x1b[7;1H"));
+    }
+
+    #[test]
+    fn test_print_with_text_block() {
+        // a row of cells of the transparent image, showing the checkerboard backdrop
+        let row = "\x1b[38;5;247m\x1b[48;5;241m▄▀▄▀\x1b[0m";
+        let config = Config {
+            x: 1,
+            truecolor: false,
+            ..Default::default()
+        };
+        let (result, size) = print_with(&AnsiBlockPrinter, &["ab", "c", "d"], &config);
+        assert_eq!(size, (8, 3));
+        assert_eq!(
+            result,
+            format!(
+                "\x1b[1;1H\x1b[1C\x1b[0m{row}\r\n\x1b[1C{row}\n\
+                 \x1b[1;8Hab\x1b[2;8Hc\x1b[3;8Hd\x1b[4;1H"
+            )
+        );
+
+        let config = Config {
+            absolute_offset: false,
+            truecolor: false,
+            ..Default::default()
+        };
+        let (result, _) = print_with(&AnsiBlockPrinter, &["ab"], &config);
+        assert_eq!(
+            result,
+            format!("\x1b[0m{row}\r\n{row}\n\x1b[2F\x1b[6Cab\r\n\n")
+        );
+    }
+
+    #[test]
+    fn test_print_with_text_saturates() {
+        let config = Config {
+            x: u16::MAX - 3,
+            y: i16::MAX,
+            ..Default::default()
+        };
+        let (result, _) = print(&["ab", "c"], &config);
+        assert!(result.ends_with("\x1b[32768;65535Hab\x1b[32769;65535Hc\x1b[32770;1H"));
+    }
+
+    #[test]
+    fn test_print_with_text_kitty() {
+        let config = Config {
+            x: 1,
+            ..Default::default()
+        };
+        let (result, size) = print_with(&KittyPrinter, &["ab", "c", "d"], &config);
+        assert_eq!(size, (8, 3));
+        assert!(result.ends_with("\x1b[1;8Hab\x1b[2;8Hc\x1b[3;8Hd\x1b[4;1H"));
+
+        // with the image at the right edge, Kitty moves the cursor to the next line by itself
+        let config = Config {
+            x: 76,
+            ..Default::default()
+        };
+        let (result, _) = print_with(&KittyPrinter, &["ab"], &config);
+        assert!(result.ends_with("\x1b\\\x1b[1;83Hab\x1b[3;1H"));
+    }
+}
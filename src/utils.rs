@@ -1,6 +1,7 @@
 use std::env;
 
 const DEFAULT_TERM_SIZE: (u16, u16) = (80, 24);
+const DEFAULT_CELL_SIZE: (u16, u16) = (10, 20);
 
 pub fn truecolor_available() -> bool {
     if let Ok(value) = env::var("COLORTERM") {
@@ -25,6 +26,25 @@ pub fn terminal_size() -> (u16, u16) {
     DEFAULT_TERM_SIZE
 }
 
+/// Try to get the size of a single terminal cell in pixels. If unsuccessful, fallback to a
+/// default (10x20). Uses [crossterm::terminal::window_size].
+#[cfg(not(test))]
+pub fn cell_size() -> (u16, u16) {
+    match crossterm::terminal::window_size() {
+        // not every terminal reports its size in pixels
+        Ok(s) if s.width > 0 && s.height > 0 && s.columns > 0 && s.rows > 0 => {
+            (s.width / s.columns, s.height / s.rows)
+        }
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// Returns a constant and only used when running the tests.
+#[cfg(test)]
+pub fn cell_size() -> (u16, u16) {
+    DEFAULT_CELL_SIZE
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
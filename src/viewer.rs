@@ -0,0 +1,381 @@
+use crate::config::{Align, Config, Fit, Overflow, Rect, Rotation};
+use crate::error::ViuResult;
+use crate::printer::{Printer, ReadKey};
+use console::{Key, Term};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+};
+use image::{DynamicImage, GenericImageView};
+use std::io::Write;
+
+// Zoom levels in percent, the first one showing the whole image
+const ZOOM_LEVELS: [u32; 9] = [100, 150, 200, 300, 400, 600, 800, 1200, 1600];
+
+/// Full-screen, interactive viewer for a list of images. It is shown in the alternate screen
+/// and controlled with the keyboard:
+///
+/// | Keys                         | Action                                         |
+/// |------------------------------|------------------------------------------------|
+/// | `+` or `=`, `-`              | Zoom in, zoom out                              |
+/// | Arrows or `h`, `j`, `k`, `l` | Pan a zoomed in image                          |
+/// | `0`                          | Show the whole image again                     |
+/// | `f`                          | Toggle between [Fit::Contain] and [Fit::Cover] |
+/// | `n`, Space or Page Down      | Next image                                     |
+/// | `p`, Backspace or Page Up    | Previous image                                 |
+/// | `i`                          | Toggle the info line at the bottom             |
+/// | `q` or Escape                | Quit                                           |
+///
+/// The images are printed according to the [Config], except that they are centered in the
+/// terminal and clipped to it. Zooming and panning select the `source_rect`.
+///
+/// ## Example
+/// ```no_run
+/// use image::{DynamicImage, RgbaImage};
+/// use viuer::{Config, Viewer};
+///
+/// let images = vec![DynamicImage::ImageRgba8(RgbaImage::new(40, 40)); 3];
+/// let mut viewer = Viewer::new(images, Config::default());
+/// viewer.run().expect("Image viewer failed.");
+/// println!("Last viewed image: {}", viewer.index());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Viewer {
+    images: Vec<DynamicImage>,
+    config: Config,
+    index: usize,
+    // index into ZOOM_LEVELS
+    zoom: usize,
+    // center of the visible region, in pixels
+    center: (u32, u32),
+    cover: bool,
+    info: bool,
+}
+
+impl Viewer {
+    /// Create a viewer for the images, starting with the first one.
+    pub fn new(images: Vec<DynamicImage>, config: Config) -> Self {
+        let mut viewer = Self {
+            images,
+            config,
+            index: 0,
+            zoom: 0,
+            center: (0, 0),
+            cover: false,
+            info: false,
+        };
+        viewer.reset();
+        viewer
+    }
+
+    /// Index of the image currently shown.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Show the viewer until it is quit. Returns immediately if there are no images.
+    pub fn run(&mut self) -> ViuResult {
+        // This is required to get a "Term" instance for "::read_key"
+        let term = Term::stdout();
+        let printer = crate::choose_printer(&self.config);
+        self.run_with(&printer, &term, &mut std::io::stdout())
+    }
+
+    fn run_with(
+        &mut self,
+        printer: &impl Printer,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+    ) -> ViuResult {
+        if self.images.is_empty() {
+            return Ok(());
+        }
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+
+        let result = self.event_loop(printer, stdin, stdout);
+
+        // leave the terminal as it was, even if drawing or clearing failed
+        let cleared = printer.clear(stdout);
+        execute!(stdout, Show, LeaveAlternateScreen)?;
+        result.and(cleared)
+    }
+
+    fn event_loop(
+        &mut self,
+        printer: &impl Printer,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+    ) -> ViuResult {
+        self.draw(printer, stdin, stdout)?;
+        loop {
+            match self.handle_key(stdin.read_key()?) {
+                Some(true) => self.draw(printer, stdin, stdout)?,
+                Some(false) => {}
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Update the state according to the key. Returns whether a redraw is needed, or `None`
+    /// to quit.
+    fn handle_key(&mut self, key: Key) -> Option<bool> {
+        let (width, height) = self.view_size();
+        let (step_x, step_y) = (std::cmp::max(1, width / 4), std::cmp::max(1, height / 4));
+        let (x, y) = self.center;
+
+        match key {
+            Key::Char('q') | Key::Escape => return None,
+            Key::Char('+') | Key::Char('=') if self.zoom + 1 < ZOOM_LEVELS.len() => self.zoom += 1,
+            Key::Char('-') if self.zoom > 0 => self.zoom -= 1,
+            Key::Char('0') => self.reset(),
+            Key::ArrowLeft | Key::Char('h') => self.center.0 = x.saturating_sub(step_x),
+            Key::ArrowRight | Key::Char('l') => self.center.0 = x + step_x,
+            Key::ArrowUp | Key::Char('k') => self.center.1 = y.saturating_sub(step_y),
+            Key::ArrowDown | Key::Char('j') => self.center.1 = y + step_y,
+            Key::Char('f') => self.cover = !self.cover,
+            Key::Char('i') => self.info = !self.info,
+            Key::Char('n') | Key::Char(' ') | Key::PageDown => {
+                self.index = (self.index + 1) % self.images.len();
+                self.reset();
+            }
+            Key::Char('p') | Key::Backspace | Key::PageUp => {
+                self.index = (self.index + self.images.len() - 1) % self.images.len();
+                self.reset();
+            }
+            _ => return Some(false),
+        }
+        // keep the visible region within the image
+        if let Some(rect) = self.visible_rect() {
+            self.center = (rect.x + rect.width / 2, rect.y + rect.height / 2);
+        }
+        Some(true)
+    }
+
+    /// Show the whole image.
+    fn reset(&mut self) {
+        self.zoom = 0;
+        let (width, height) = self.image_size();
+        self.center = (width / 2, height / 2);
+    }
+
+    /// Size of the current image as printed, in pixels.
+    fn image_size(&self) -> (u32, u32) {
+        let Some(img) = self.images.get(self.index) else {
+            return (0, 0);
+        };
+        let (width, height) = img.dimensions();
+        match self.config.rotate {
+            Rotation::Deg90 | Rotation::Deg270 => (height, width),
+            Rotation::None | Rotation::Deg180 => (width, height),
+        }
+    }
+
+    /// Size of the visible region at the current zoom level, in pixels.
+    fn view_size(&self) -> (u32, u32) {
+        let (width, height) = self.image_size();
+        let zoom = ZOOM_LEVELS[self.zoom];
+        (
+            std::cmp::max(1, width * 100 / zoom),
+            std::cmp::max(1, height * 100 / zoom),
+        )
+    }
+
+    /// The visible region around the center, or `None` if the whole image is visible.
+    fn visible_rect(&self) -> Option<Rect> {
+        if self.zoom == 0 {
+            return None;
+        }
+        let (img_width, img_height) = self.image_size();
+        let (width, height) = self.view_size();
+        let start = |center: u32, size: u32, max: u32| {
+            std::cmp::min(center.saturating_sub(size / 2), max.saturating_sub(size))
+        };
+        Some(Rect::new(
+            start(self.center.0, width, img_width),
+            start(self.center.1, height, img_height),
+            width,
+            height,
+        ))
+    }
+
+    fn image_config(&self) -> Config {
+        Config {
+            absolute_offset: true,
+            x: 0,
+            y: 0,
+            align: Some(Align::default()),
+            align_area: None,
+            overflow: Overflow::Clip,
+            restore_cursor: false,
+            source_rect: self.visible_rect(),
+            width: None,
+            height: None,
+            fit: match self.cover {
+                true => Fit::Cover,
+                false => Fit::Contain,
+            },
+            fit_align: Align::default(),
+            ..self.config.clone()
+        }
+    }
+
+    fn info_line(&self) -> String {
+        let (width, height) = self.image_size();
+        format!(
+            "{}/{}  {}x{}  {}%  {}",
+            self.index + 1,
+            self.images.len(),
+            width,
+            height,
+            ZOOM_LEVELS[self.zoom],
+            if self.cover { "cover" } else { "contain" }
+        )
+    }
+
+    fn draw(
+        &self,
+        printer: &impl Printer,
+        stdin: &impl ReadKey,
+        stdout: &mut impl Write,
+    ) -> ViuResult {
+        printer.clear(stdout)?;
+        let img = &self.images[self.index];
+        printer.print(stdin, stdout, img, &self.image_config())?;
+
+        if self.info {
+            // the last line is left free by the layout
+            let (term_w, term_h) = self.config.terminal_size();
+            let line: String = self.info_line().chars().take(term_w as usize).collect();
+            execute!(stdout, MoveTo(0, term_h.saturating_sub(1)))?;
+            write!(stdout, "{line}")?;
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::iTermPrinter;
+    use crate::printer::read_key::test_utils::TestKeys;
+
+    fn viewer(count: usize) -> Viewer {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(100, 50));
+        Viewer::new(vec![img; count], Config::default())
+    }
+
+    fn run(viewer: &mut Viewer, keys: &[Key]) -> (String, ViuResult) {
+        let mut vec = Vec::new();
+        let stdin = TestKeys::new(keys);
+        let result = viewer.run_with(&iTermPrinter, &stdin, &mut vec);
+        assert!(stdin.reached_end());
+        (String::from_utf8(vec).unwrap(), result)
+    }
+
+    #[test]
+    fn test_viewer_navigation() {
+        let mut viewer = viewer(3);
+        let keys = [
+            Key::Char('n'),
+            Key::PageDown,
+            Key::Char('x'),
+            Key::Char(' '),
+            Key::Char('p'),
+            Key::Char('q'),
+        ];
+        let (result, status) = run(&mut viewer, &keys);
+        assert!(status.is_ok());
+        assert_eq!(viewer.index(), 2);
+
+        // an unknown key does not redraw
+        assert_eq!(result.matches("\x1b]1337;File=inline=1").count(), 5);
+        assert!(result.starts_with("\x1b[?1049h\x1b[?25l"));
+        assert!(result.ends_with("\x1b[?25h\x1b[?1049l"));
+    }
+
+    // Output failing to clear the screen, which keeps everything else
+    struct FailingClear(Vec<u8>);
+
+    impl Write for FailingClear {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if buf.starts_with(b"\x1b[2J") {
+                return Err(std::io::Error::other("clear failed"));
+            }
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_viewer_restores_terminal() {
+        let mut stdout = FailingClear(Vec::new());
+        let result = viewer(1).run_with(&iTermPrinter, &TestKeys::new(&[]), &mut stdout);
+
+        assert!(result.is_err());
+        let output = String::from_utf8(stdout.0).unwrap();
+        assert!(output.ends_with("\x1b[?25h\x1b[?1049l"), "{output:?}");
+    }
+
+    #[test]
+    fn test_viewer_zoom_pan() {
+        let mut viewer = viewer(1);
+        viewer.handle_key(Key::Char('+'));
+        assert_eq!(viewer.visible_rect(), Some(Rect::new(17, 9, 66, 33)));
+
+        viewer.handle_key(Key::ArrowRight);
+        assert_eq!(viewer.visible_rect(), Some(Rect::new(33, 9, 66, 33)));
+        // cannot pan past the edge
+        viewer.handle_key(Key::Char('l'));
+        viewer.handle_key(Key::Char('l'));
+        assert_eq!(viewer.visible_rect(), Some(Rect::new(34, 9, 66, 33)));
+        viewer.handle_key(Key::Char('k'));
+        assert_eq!(viewer.visible_rect(), Some(Rect::new(34, 1, 66, 33)));
+        assert_eq!(
+            viewer.image_config().source_rect,
+            Some(Rect::new(34, 1, 66, 33))
+        );
+
+        viewer.handle_key(Key::Char('-'));
+        assert_eq!(viewer.visible_rect(), None);
+        viewer.handle_key(Key::Char('='));
+        viewer.handle_key(Key::Char('0'));
+        assert_eq!(viewer.image_config().source_rect, None);
+    }
+
+    #[test]
+    fn test_viewer_info_and_fit() {
+        let mut viewer = viewer(2);
+        let (result, _) = run(&mut viewer, &[Key::Char('i'), Key::Char('f'), Key::Escape]);
+        assert!(result.contains("\x1b[24;1H1/2  100x50  100%  contain"));
+        assert!(result.contains("\x1b[24;1H1/2  100x50  100%  cover"));
+        assert_eq!(viewer.image_config().fit, Fit::Cover);
+
+        // the info line is at the bottom of the configured terminal size
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(100, 50));
+        let config = Config {
+            terminal_size: Some((12, 10)),
+            ..Default::default()
+        };
+        let mut viewer = Viewer::new(vec![img], config);
+        let (result, _) = run(&mut viewer, &[Key::Char('i'), Key::Escape]);
+        assert!(result.contains("\x1b[10;1H1/1  100x50"));
+        assert!(!result.contains("100x50  100%"));
+    }
+
+    #[test]
+    fn test_viewer_leaves_alternate_screen_on_error() {
+        let mut viewer = viewer(1);
+        let (result, status) = run(&mut viewer, &[Key::Char('+')]);
+        assert!(status.is_err());
+        assert!(result.ends_with("\x1b[?1049l"));
+
+        let (result, status) = run(&mut Viewer::new(vec![], Config::default()), &[]);
+        assert!(status.is_ok());
+        assert!(result.is_empty());
+    }
+}
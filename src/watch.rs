@@ -0,0 +1,250 @@
+use crate::config::{Config, Rect};
+use crate::error::ViuResult;
+use crate::printer::layout::Layout;
+use crate::printer::orientation::{self, orient};
+use crate::printer::{BlockRenderer, Printer, PrinterType, ReadKey, DEFAULT_KITTY_IMAGE_ID};
+use console::Term;
+use crossterm::{
+    cursor::{MoveRight, MoveTo, MoveToPreviousLine},
+    execute,
+};
+use image::{DynamicImage, ImageReader};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// How often the file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Print an image file and print it again in place whenever it changes, for example because
+/// a script regenerates it. Never returns, unless printing fails.
+///
+/// The file is checked a few times per second. A change is only picked up once the file stops
+/// changing, so that partially written files are not shown. If the file cannot be decoded,
+/// the previous image stays on screen until the next change.
+///
+/// The block printer only redraws the cells that changed, see [BlockRenderer]. With Kitty, the
+/// image is replaced using `kitty_image_id`, which defaults to a fixed id. Other protocols
+/// print over the previous image after clearing it. The feature is available only with the
+/// `print-file` feature.
+///
+/// ## Example
+/// ```no_run
+/// use viuer::{watch_file, Config};
+///
+/// watch_file("plot.png", &Config::default()).expect("Image printing failed.");
+/// ```
+pub fn watch_file<P: AsRef<Path>>(path: P, config: &Config) -> ViuResult {
+    let config = Config {
+        kitty_image_id: config.kitty_image_id.or(Some(DEFAULT_KITTY_IMAGE_ID)),
+        restore_cursor: false,
+        ..config.clone()
+    };
+    let mut watcher = FileWatcher::new(path.as_ref(), &config);
+
+    // This is required to get a "Term" instance for "::read_key"
+    let term = Term::stdout();
+    let mut stdout = std::io::stdout();
+    let printer = crate::choose_printer(&config);
+    let mut renderer = BlockRenderer::new();
+    let mut previous = None;
+
+    loop {
+        if let Some(img) = watcher.poll() {
+            match printer {
+                PrinterType::Block => {
+                    renderer.render(&img, &config)?;
+                }
+                _ => {
+                    let area = redraw(&printer, &term, &mut stdout, &img, &config, previous)?;
+                    previous = Some(area);
+                }
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Modification time and length of a file, which tell whether it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Version {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Version {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Decodes a file whenever it has changed.
+struct FileWatcher {
+    path: PathBuf,
+    config: Config,
+    // the last version that was decoded, successfully or not
+    decoded: Option<Version>,
+    // the version seen by the previous poll
+    pending: Option<Version>,
+}
+
+impl FileWatcher {
+    fn new(path: &Path, config: &Config) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            config: config.clone(),
+            decoded: None,
+            pending: None,
+        }
+    }
+
+    /// Check the file and return the image if it changed since the last decoded version.
+    /// Changes are debounced: the file has to be the same for two polls in a row. Missing and
+    /// undecodable files are ignored.
+    fn poll(&mut self) -> Option<DynamicImage> {
+        let version = Version::of(&self.path);
+        if version.is_none() || version == self.decoded {
+            return None;
+        }
+        if version != self.pending {
+            self.pending = version;
+            return None;
+        }
+
+        self.decoded = version;
+        let reader = ImageReader::open(&self.path).ok()?;
+        orientation::decode(reader, &self.config)
+            .ok()
+            .map(|(img, _)| img)
+    }
+}
+
+/// Clear the area of the previous image and print the new one. Returns the area it covers:
+/// the column, the row relative to the `y` offset, and the size in cells.
+fn redraw(
+    printer: &impl Printer,
+    stdin: &impl ReadKey,
+    stdout: &mut impl Write,
+    img: &DynamicImage,
+    config: &Config,
+    previous: Option<Rect>,
+) -> ViuResult<Rect> {
+    if let Some(area) = previous {
+        clear_area(stdout, area, config)?;
+    }
+    printer.print(stdin, stdout, img, config)?;
+
+    let layout = Layout::new(&orient(img, config), config)?;
+    Ok(Rect::new(
+        config.x as u32 + layout.box_x,
+        layout.box_y,
+        layout.box_width,
+        layout.box_height,
+    ))
+}
+
+/// Overwrite the area with spaces. In relative mode, the cursor is expected below the area,
+/// and is moved back to where the image was printed from.
+fn clear_area(stdout: &mut impl Write, area: Rect, config: &Config) -> ViuResult {
+    let blank = " ".repeat(area.width as usize);
+    if config.absolute_offset {
+        for row in area.y..area.y + area.height {
+            execute!(stdout, MoveTo(area.x as u16, config.y as u16 + row as u16))?;
+            write!(stdout, "{blank}")?;
+        }
+        return Ok(());
+    }
+
+    execute!(stdout, MoveToPreviousLine(area.height as u16))?;
+    for _ in 0..area.height {
+        // Some terminals interpret 0 as 1, see MoveRight documentation
+        if area.x > 0 {
+            execute!(stdout, MoveRight(area.x as u16))?;
+        }
+        write!(stdout, "{blank}\r\n")?;
+    }
+    let printed_from = config.y as i64 + (area.y + area.height) as i64;
+    if printed_from > 0 {
+        execute!(stdout, MoveToPreviousLine(printed_from as u16))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::iTermPrinter;
+    use crate::printer::read_key::test_utils::TestKeys;
+
+    fn save(path: &Path, width: u32) {
+        image::RgbaImage::new(width, 2).save(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plot.png");
+        let mut watcher = FileWatcher::new(&path, &Config::default());
+        assert!(watcher.poll().is_none());
+
+        save(&path, 2);
+        // the file has to stay the same for a poll
+        assert!(watcher.poll().is_none());
+        assert_eq!(watcher.poll().unwrap().width(), 2);
+        assert!(watcher.poll().is_none());
+
+        // a partial write, which is not retried until the file changes again
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n").unwrap();
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().is_none());
+
+        save(&path, 3);
+        assert!(watcher.poll().is_none());
+        assert_eq!(watcher.poll().unwrap().width(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_none());
+    }
+
+    fn redraw_twice(config: &Config) -> String {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4));
+        let mut vec = Vec::new();
+        let stdin = TestKeys::new(&[]);
+
+        let area = redraw(&iTermPrinter, &stdin, &mut vec, &img, config, None).unwrap();
+        assert_eq!(area, Rect::new(config.x as u32, 0, 4, 2));
+        vec.clear();
+        redraw(&iTermPrinter, &stdin, &mut vec, &img, config, Some(area)).unwrap();
+        String::from_utf8(vec).unwrap()
+    }
+
+    #[test]
+    fn test_redraw_absolute() {
+        let config = Config {
+            x: 1,
+            y: 2,
+            ..Default::default()
+        };
+        let result = redraw_twice(&config);
+        assert!(result.starts_with("\x1b[3;2H    \x1b[4;2H    \x1b[3;2H\x1b]1337"));
+    }
+
+    #[test]
+    fn test_redraw_relative() {
+        let config = Config {
+            absolute_offset: false,
+            x: 1,
+            y: 1,
+            ..Default::default()
+        };
+        let result = redraw_twice(&config);
+        assert!(
+            result.starts_with("\x1b[2F\x1b[1C    \r\n\x1b[1C    \r\n\x1b[3F\n\x1b[1C\x1b]1337")
+        );
+    }
+}